@@ -1,13 +1,18 @@
 use crate::util::{git_add_tag, next_major, next_minor, next_patch, next_phase, next_pre};
-use clap::{command, Arg, Command};
+use clap::{command, Arg, ArgAction, ArgMatches, Command};
+use clap_complete::engine::{ArgValueCandidates, CompletionCandidate};
+use clap_complete::CompleteEnv;
 use log::{error, trace, warn};
 use log4rs::{self, config::RawConfig};
 use rust_embed::Embed;
 use semver::Version;
 use std::env;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
+mod plugin;
+mod template;
 mod util;
 
 #[derive(Embed)]
@@ -16,6 +21,11 @@ mod util;
 struct Asset;
 
 fn main() -> anyhow::Result<()> {
+    // the completion scripts run cot with COMPLETE set to ask for candidates
+    CompleteEnv::with_factory(cli)
+        .var(plugin::COMPLETE_VAR)
+        .bin("cot")
+        .complete();
     // for file in Asset::iter() {
     //     println!("{}", file.as_ref());
     // }
@@ -48,129 +58,7 @@ fn main() -> anyhow::Result<()> {
     };
     log::set_max_level(logging_level);
 
-    let mut cmd = command!()
-        .help_template(
-            "{about}
-{author}
-
-Commands:
-{subcommands}",
-        )
-        .subcommand(
-            Command::new("install")
-                .about("Install/add/i Cot or plugins")
-                .aliases(["add", "i"]),
-        )
-        .subcommand(
-            Command::new("remove")
-                .about("Remove/delete/del/rm plugin")
-                .aliases(["rm", "delete", "del"]),
-        )
-        .subcommand(
-            Command::new("use")
-                .about("Use/active plugin")
-                .aliases(["active"]),
-        )
-        .subcommand(
-            Command::new("list")
-                .about("List/ls plugins")
-                .aliases(["ls"]),
-        )
-        .subcommand(
-            Command::new("init")
-                .about("Init/create new project")
-                .aliases(["create"]),
-        )
-        .subcommand(
-            Command::new("huggingface")
-                .about("Huggingface/hf directory")
-                .aliases(["hf"]),
-        )
-        .subcommand(
-            Command::new("huggingface-datasets")
-                .about("Huggingface-datasets/hd <id>'s directory")
-                .aliases(["hd"])
-                .arg(Arg::new("id").help("datasets id, such as 'sentence-transformers/all-nli'")),
-        )
-        .subcommand(
-            Command::new("huggingface-models")
-                .about("Huggingface-models/hm <id>'s directory")
-                .aliases(["hm"])
-                .arg(Arg::new("id").help("model id, such as 'baai/bge-large-zh-v1.5'")),
-        )
-        .subcommand(
-            Command::new("xf")
-                .about("Extract <filename.tar.gz>")
-                .arg(
-                    Arg::new("directory")
-                        .short('C')
-                        .long("Extract <filename.tar.gz> into <directory>")
-                        .default_value("."),
-                )
-                .arg(Arg::new("filename")),
-        )
-        .subcommand(
-            Command::new("unzip")
-                .about("Extract <filename.zip>")
-                .arg(
-                    Arg::new("directory")
-                        .short('d')
-                        .long("Extract <filename.zip> into <directory>")
-                        .default_value("."),
-                )
-                .arg(Arg::new("filename")),
-        )
-        .subcommand(
-            Command::new("tag")
-                .about("Tag [current|next|date|hash|show]")
-                .aliases(["t"])
-                .subcommand(Command::new("current").about("Current tag").aliases(["c"]))
-                .subcommand(
-                    Command::new("next")
-                        .about("Next [major|minor|patch|pre|phase] of current tag")
-                        .aliases(["n"])
-                        .subcommand(
-                            Command::new("major")
-                                .about("Next major version of current tag")
-                                .aliases(["a"]),
-                        )
-                        .subcommand(
-                            Command::new("minor")
-                                .about("Next minor version of current tag")
-                                .aliases(["i"]),
-                        )
-                        .subcommand(
-                            Command::new("patch")
-                                .about("Next patch version of current tag")
-                                .aliases(["p"]),
-                        )
-                        .subcommand(
-                            Command::new("phase")
-                                .about("Next phase version of current tag")
-                                .aliases(["s"]),
-                        )
-                        .subcommand(
-                            Command::new("pre")
-                                .about("Next pre version of current tag")
-                                .aliases(["r"]),
-                        ),
-                )
-                .subcommand(
-                    Command::new("date")
-                        .about("Date of current tag")
-                        .aliases(["d"]),
-                )
-                .subcommand(
-                    Command::new("hash")
-                        .about("Hash of current tag")
-                        .aliases(["h"]),
-                )
-                .subcommand(
-                    Command::new("show")
-                        .about("Show information about tag")
-                        .aliases(["s"]),
-                ),
-        );
+    let mut cmd = cli();
     let matches = cmd.clone().get_matches();
     let subcommand = matches.subcommand();
     match subcommand {
@@ -179,11 +67,481 @@ Commands:
             Ok(())
         }
         Some((cmd_name, args)) => match cmd_name {
-            "huggingface" => {
-                let hf_home = util::hf_home()?;
-                print!("{}", hf_home);
+            "install" => {
+                let home = plugin::cot_home()?;
+                let source = plugin::Source::from_env(args.get_one::<String>("source"), &home);
+                let options = install_options(&home, args, args.get_flag("force"));
+                let spec = match args.get_one::<String>("plugin") {
+                    Some(spec) => Some(plugin::PluginSpec::parse(spec)?),
+                    None => None,
+                };
+                let outcomes = match spec {
+                    _ if args.get_flag("locked") => {
+                        let (path, outcomes) = plugin::install_locked(
+                            &home,
+                            &source,
+                            &env::current_dir()?,
+                            spec.as_ref(),
+                            &options,
+                        )?;
+                        trace!("installing from {}", path.display());
+                        outcomes
+                    }
+                    Some(spec) => {
+                        vec![plugin::install(&home, &source, &spec, &options)?]
+                    }
+                    None => {
+                        let (path, outcomes) =
+                            plugin::install_pinned(&home, &source, &env::current_dir()?, &options)?;
+                        trace!("installing pins of {}", path.display());
+                        outcomes
+                    }
+                };
+                plugin::reshim(&home, &env::current_exe()?)?;
+                for outcome in outcomes {
+                    println!("{}", outcome);
+                }
                 Ok(())
             }
+            "lock" => {
+                let home = plugin::cot_home()?;
+                let source = plugin::Source::from_env(args.get_one::<String>("source"), &home);
+                let options = install_options(&home, args, false);
+                let (path, lock) =
+                    plugin::lock(&home, &source, &env::current_dir()?, &options.fetch)?;
+                for (name, locked) in &lock.plugins {
+                    println!("{} => {}@{}", locked.pin, name, locked.version);
+                }
+                println!("written {}", path.display());
+                Ok(())
+            }
+            "current" => {
+                let home = plugin::cot_home()?;
+                let name = args.get_one::<String>("plugin");
+                let entries: Vec<plugin::Current> = plugin::current(&home, &env::current_dir()?)?
+                    .into_iter()
+                    .filter(|e| name.is_none_or(|n| n == &e.name))
+                    .collect();
+                let width = entries.iter().map(|e| e.name.len()).max().unwrap_or(0);
+                for entry in entries {
+                    let version = match (&entry.version, &entry.pinned) {
+                        (Some(v), _) => v.to_string(),
+                        (None, Some(pinned)) => format!("{} (not installed)", pinned),
+                        (None, None) => "(none)".to_string(),
+                    };
+                    let origin = match &entry.origin {
+                        plugin::Origin::Project(path) => path.display().to_string(),
+                        plugin::Origin::Global => "global".to_string(),
+                    };
+                    println!("{:<width$}  {}  {}", entry.name, version, origin);
+                }
+                Ok(())
+            }
+            "remove" => {
+                let spec = plugin::PluginSpec::parse(args.get_one::<String>("plugin").unwrap())?;
+                let home = plugin::cot_home()?;
+                let plan = plugin::plan_removal(
+                    &home,
+                    &spec,
+                    args.get_flag("all"),
+                    args.get_flag("force"),
+                    &env::current_dir()?,
+                )?;
+                if args.get_flag("dry-run") {
+                    for path in &plan.paths {
+                        println!("{}", path.display());
+                    }
+                    return Ok(());
+                }
+                plugin::remove(&home, &plan)?;
+                plugin::reshim(&home, &env::current_exe()?)?;
+                for version in &plan.versions {
+                    println!("{}@{} removed", plan.name, version);
+                }
+                Ok(())
+            }
+            "use" => {
+                let spec = plugin::PluginSpec::parse(args.get_one::<String>("plugin").unwrap())?;
+                let home = plugin::cot_home()?;
+                let current_dir = env::current_dir()?;
+                let project = args.get_flag("local").then_some(current_dir.as_path());
+                let active = plugin::use_version(&home, &spec, project)?;
+                plugin::reshim(&home, &env::current_exe()?)?;
+                match active.origin {
+                    plugin::Origin::Project(path) => {
+                        println!(
+                            "{}@{} pinned in {}",
+                            active.name,
+                            active.version,
+                            path.display()
+                        );
+                    }
+                    plugin::Origin::Global => {
+                        println!("{}@{} is now active", active.name, active.version);
+                        // a project pin keeps winning inside its directory tree
+                        if let Ok(Some(pinned)) =
+                            plugin::active_version(&home, &active.name, &current_dir)
+                        {
+                            if let plugin::Origin::Project(path) = pinned.origin {
+                                println!(
+                                    "{}@{} pinned in {} takes precedence here",
+                                    pinned.name,
+                                    pinned.version,
+                                    path.display()
+                                );
+                            }
+                        }
+                    }
+                }
+                Ok(())
+            }
+            "upgrade" => {
+                let home = plugin::cot_home()?;
+                let source = plugin::Source::from_env(args.get_one::<String>("source"), &home);
+                let spec = match args.get_one::<String>("plugin") {
+                    Some(spec) => Some(plugin::PluginSpec::parse(spec)?),
+                    None => None,
+                };
+                let upgrades = plugin::plan_upgrades(&home, &source, spec.as_ref())?;
+                if upgrades.is_empty() {
+                    println!("everything is up to date");
+                    return Ok(());
+                }
+                if args.get_flag("dry-run") {
+                    for upgrade in &upgrades {
+                        println!("{} {} -> {}", upgrade.name, upgrade.from, upgrade.to);
+                    }
+                    return Ok(());
+                }
+                let options = install_options(&home, args, false);
+                let outcomes =
+                    plugin::upgrade(&home, &source, &upgrades, &options, args.get_flag("use"))?;
+                plugin::reshim(&home, &env::current_exe()?)?;
+                for (upgrade, outcome) in upgrades.iter().zip(outcomes) {
+                    println!("{} (was {})", outcome, upgrade.from);
+                }
+                Ok(())
+            }
+            "outdated" => {
+                let home = plugin::cot_home()?;
+                let source = plugin::Source::from_env(args.get_one::<String>("source"), &home);
+                let entries = plugin::outdated(&home, &source, &env::current_dir()?)?;
+                if args.get_flag("json") {
+                    println!("{}", serde_json::to_string_pretty(&entries)?);
+                    return Ok(());
+                }
+                if entries.is_empty() {
+                    println!("everything is up to date");
+                    return Ok(());
+                }
+                let width = entries
+                    .iter()
+                    .map(|e| e.name.len())
+                    .max()
+                    .unwrap_or(0)
+                    .max(4);
+                println!(
+                    "{:<width$}  {:<10}  {:<10}  {:<10}  GAP",
+                    "NAME", "CURRENT", "WANTED", "LATEST"
+                );
+                for entry in entries {
+                    let mut line = format!(
+                        "{:<width$}  {:<10}  {:<10}  {:<10}  {:<10}",
+                        entry.name,
+                        entry.current.to_string(),
+                        entry.wanted.to_string(),
+                        entry.latest.to_string(),
+                        entry.gap.to_string()
+                    );
+                    if let (Some(pin), Some(path)) = (&entry.pin, &entry.pinned_in) {
+                        line.push_str(&format!("  {} in {}", pin, path.display()));
+                    }
+                    println!("{}", line.trim_end());
+                }
+                Ok(())
+            }
+            "self-update" => {
+                let home = plugin::cot_home()?;
+                let release = args.get_one::<String>("release").unwrap();
+                let update = plugin::self_update(
+                    &home,
+                    release,
+                    &env::current_exe()?,
+                    args.get_flag("force"),
+                )?;
+                if update.replaced {
+                    println!(
+                        "cot {} -> {} in {}",
+                        update.from,
+                        update.to,
+                        update.path.display()
+                    );
+                } else {
+                    println!("cot {} is already installed", update.to);
+                }
+                Ok(())
+            }
+            "gc" => {
+                let home = plugin::cot_home()?;
+                let dry_run = args.get_flag("dry-run");
+                let days = *args.get_one::<u64>("max-age").unwrap();
+                let max_size = match args.get_one::<String>("max-size") {
+                    Some(size) => Some(util::parse_size(size)?),
+                    None => None,
+                };
+                let options = plugin::GcOptions {
+                    dry_run,
+                    cache: Some(plugin::download_cache(&home)),
+                    max_age: Some(Duration::from_secs(days * 24 * 60 * 60)),
+                    max_size,
+                };
+                let report = plugin::gc(&home, &env::current_dir()?, &options)?;
+                let verb = if dry_run { "would remove" } else { "removed" };
+                for (name, version, size) in &report.versions {
+                    println!(
+                        "{} {}@{} ({})",
+                        verb,
+                        name,
+                        version,
+                        util::format_size(*size)
+                    );
+                }
+                for (path, size) in &report.archives {
+                    println!("{} {} ({})", verb, path.display(), util::format_size(*size));
+                }
+                for path in &report.forgotten {
+                    println!("forgot {}, it no longer exists", path.display());
+                }
+                if !dry_run {
+                    plugin::reshim(&home, &env::current_exe()?)?;
+                }
+                println!(
+                    "{} {}",
+                    if dry_run {
+                        "would reclaim"
+                    } else {
+                        "reclaimed"
+                    },
+                    util::format_size(report.reclaimed())
+                );
+                Ok(())
+            }
+            "reshim" => {
+                let home = plugin::cot_home()?;
+                let shims = plugin::reshim(&home, &env::current_exe()?)?;
+                println!(
+                    "{} shims in {}",
+                    shims.len(),
+                    plugin::shims_dir(&home).display()
+                );
+                Ok(())
+            }
+            "shim" => {
+                let binary = args.get_one::<String>("binary").unwrap();
+                let shim_args: Vec<String> = args
+                    .get_many::<String>("args")
+                    .unwrap_or_default()
+                    .cloned()
+                    .collect();
+                let home = plugin::cot_home()?;
+                plugin::run_shim(&home, binary, &shim_args, &env::current_dir()?)
+            }
+            "exec" => {
+                let spec = plugin::PluginSpec::parse(args.get_one::<String>("plugin").unwrap())?;
+                let program = args.get_one::<String>("command").unwrap();
+                let exec_args: Vec<String> = args
+                    .get_many::<String>("args")
+                    .unwrap_or_default()
+                    .cloned()
+                    .collect();
+                let home = plugin::cot_home()?;
+                plugin::exec_version(&home, &spec, program, &exec_args)
+            }
+            "env" => {
+                let shell = match args.get_one::<String>("shell") {
+                    Some(shell) => plugin::Shell::parse(shell)?,
+                    None => plugin::Shell::detect(),
+                };
+                let home = plugin::cot_home()?;
+                let active_env = plugin::active_env(&home, &env::current_dir()?)?;
+                if args.get_flag("hook") {
+                    let previous = env::var(plugin::ENV_VARS).unwrap_or_default();
+                    print!("{}", plugin::render_hook(shell, &active_env, &previous));
+                } else {
+                    print!("{}", plugin::render_env(shell, &active_env));
+                }
+                Ok(())
+            }
+            "completions" => {
+                let shell = plugin::Shell::parse(args.get_one::<String>("shell").unwrap())?;
+                plugin::write_completions(
+                    shell,
+                    &mut cli(),
+                    &env::current_exe()?,
+                    &mut io::stdout(),
+                )
+            }
+            "shell-init" => {
+                let shell = plugin::Shell::parse(args.get_one::<String>("shell").unwrap())?;
+                let home = plugin::cot_home()?;
+                print!(
+                    "{}",
+                    plugin::shell_init(shell, &home, &env::current_exe()?)?
+                );
+                Ok(())
+            }
+            "which" => {
+                let binary = args.get_one::<String>("binary").unwrap();
+                let home = plugin::cot_home()?;
+                let resolved = plugin::resolve_binary(&home, binary, &env::current_dir()?)?;
+                if args.get_flag("path") {
+                    println!("{}", resolved.path.display());
+                    return Ok(());
+                }
+                let origin = match &resolved.active.origin {
+                    plugin::Origin::Project(path) => format!("pinned in {}", path.display()),
+                    plugin::Origin::Global => "global".to_string(),
+                };
+                println!(
+                    "{}@{} ({}): {}",
+                    resolved.active.name,
+                    resolved.active.version,
+                    origin,
+                    resolved.path.display()
+                );
+                Ok(())
+            }
+            "list" => {
+                let home = plugin::cot_home()?;
+                let json = args.get_flag("json");
+                if args.get_flag("available") {
+                    let source = plugin::Source::from_env(args.get_one::<String>("source"), &home);
+                    let entries = plugin::list_available(&home, &source)?;
+                    if json {
+                        println!("{}", serde_json::to_string_pretty(&entries)?);
+                        return Ok(());
+                    }
+                    let width = entries.iter().map(|e| e.name.len()).max().unwrap_or(0);
+                    for entry in entries {
+                        let installed = if entry.installed { " installed" } else { "" };
+                        println!("{:<width$}  {}{}", entry.name, entry.version, installed);
+                    }
+                    return Ok(());
+                }
+                let entries = plugin::list_installed(&home, &env::current_dir()?)?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&entries)?);
+                    return Ok(());
+                }
+                let width = entries.iter().map(|e| e.name.len()).max().unwrap_or(0);
+                for entry in entries {
+                    let active = match (&entry.active, &entry.pinned_in) {
+                        (true, Some(path)) => format!(" * ({})", path.display()),
+                        (true, None) => " *".to_string(),
+                        _ => "".to_string(),
+                    };
+                    println!("{:<width$}  {}{}", entry.name, entry.version, active);
+                }
+                Ok(())
+            }
+            "huggingface" => match args.subcommand() {
+                Some(("ls", args)) => {
+                    let cache = util::hf_hub_cache_dir()?;
+                    let mut repos = util::hf_cached_repos(&cache)?;
+                    if let Some(repo_type) = args.get_one::<String>("type") {
+                        repos.retain(|repo| &repo.repo_type == repo_type);
+                    }
+                    if args.get_flag("json") {
+                        println!("{}", serde_json::to_string_pretty(&repos)?);
+                        return Ok(());
+                    }
+                    let width = repos.iter().map(|r| r.id.len()).max().unwrap_or(0).max(2);
+                    println!(
+                        "{:<width$}  {:<7}  {:>9}  {:>10}  REFS",
+                        "ID", "TYPE", "SNAPSHOTS", "SIZE"
+                    );
+                    for repo in repos {
+                        let refs: Vec<&str> = repo.refs.keys().map(String::as_str).collect();
+                        println!(
+                            "{:<width$}  {:<7}  {:>9}  {:>10}  {}",
+                            repo.id,
+                            repo.repo_type,
+                            repo.snapshots,
+                            util::format_size(repo.size),
+                            refs.join(",")
+                        );
+                    }
+                    Ok(())
+                }
+                Some(("du", args)) => {
+                    let mut size = util::hf_cache_size(&util::hf_hub_cache_dir()?)?;
+                    // the biggest first
+                    size.repos.sort_by_key(|repo| std::cmp::Reverse(repo.size));
+                    if args.get_flag("json") {
+                        println!("{}", serde_json::to_string_pretty(&size)?);
+                        return Ok(());
+                    }
+                    let width = size
+                        .repos
+                        .iter()
+                        .map(|r| r.id.len())
+                        .max()
+                        .unwrap_or(0)
+                        .max(2);
+                    println!(
+                        "{:<width$}  {:<7}  {:>9}  {:>10}  {:>10}  {:>12}",
+                        "ID", "TYPE", "REVISIONS", "SIZE", "SHARED", "UNREFERENCED"
+                    );
+                    for repo in &size.repos {
+                        println!(
+                            "{:<width$}  {:<7}  {:>9}  {:>10}  {:>10}  {:>12}",
+                            repo.id,
+                            repo.repo_type,
+                            repo.revisions.len(),
+                            util::format_size(repo.size),
+                            util::format_size(repo.shared),
+                            util::format_size(repo.unreferenced)
+                        );
+                        if !args.get_flag("revisions") {
+                            continue;
+                        }
+                        for revision in &repo.revisions {
+                            let refs = match revision.refs.is_empty() {
+                                true => "".to_string(),
+                                false => format!(" ({})", revision.refs.join(",")),
+                            };
+                            println!(
+                                "  {}{}: {} files, {}, {} shared",
+                                revision.commit,
+                                refs,
+                                revision.files,
+                                util::format_size(revision.size),
+                                util::format_size(revision.shared)
+                            );
+                        }
+                    }
+                    println!(
+                        "total {} in {} repos, {}",
+                        util::format_size(size.size),
+                        size.repos.len(),
+                        size.path.display()
+                    );
+                    // what the datasets library made of them is kept apart from the hub cache
+                    if let Ok(datasets) = util::hf_datasets_cache() {
+                        println!(
+                            "datasets cache {}, {}",
+                            util::format_size(util::dir_size(Path::new(&datasets))),
+                            datasets
+                        );
+                    }
+                    Ok(())
+                }
+                _ => {
+                    let hf_home = util::hf_home()?;
+                    print!("{}", hf_home);
+                    Ok(())
+                }
+            },
             "huggingface-models" => {
                 let id = args.get_one::<String>("id");
                 if id.is_none() {
@@ -204,6 +562,74 @@ Commands:
                 print!("{}", hf_datasets);
                 Ok(())
             }
+            "init" => {
+                if args.get_flag("list") {
+                    let templates = template::builtin_templates()?;
+                    let width = templates.iter().map(|t| t.name.len()).max().unwrap_or(0);
+                    for t in templates {
+                        println!("{:width$}  {}", t.name, t.manifest.description);
+                    }
+                    return Ok(());
+                }
+                let mut options = template::InitOptions {
+                    interactive: template::is_interactive(),
+                    cache: Some(template::template_cache(&plugin::cot_home()?)),
+                    run_actions: !args.get_flag("no-actions"),
+                    ..template::InitOptions::default()
+                };
+                for set in args.get_many::<String>("set").unwrap_or_default() {
+                    let (key, value) = template::parse_var(set)?;
+                    options.vars.insert(key, value);
+                }
+                if args.get_flag("update") {
+                    let report = template::update_project(Path::new("."), &options)?;
+                    if report.up_to_date() {
+                        println!("already up to date with {}", report.to);
+                        return Ok(());
+                    }
+                    println!("updated from {} to {}", report.from, report.to);
+                    for file in &report.added {
+                        println!("added {}", file.display());
+                    }
+                    for file in &report.updated {
+                        println!("updated {}", file.display());
+                    }
+                    for file in &report.removed {
+                        println!("removed {}", file.display());
+                    }
+                    for file in &report.kept {
+                        println!(
+                            "kept {}, removed from the template but changed here",
+                            file.display()
+                        );
+                    }
+                    if !report.conflicts.is_empty() {
+                        for (file, count) in &report.conflicts {
+                            println!("conflict {} ({} conflicts)", file.display(), count);
+                        }
+                        for file in &report.copies {
+                            println!("the template's version is in {}", file.display());
+                        }
+                        return Err(anyhow::anyhow!(
+                            "{} files have conflicts to resolve",
+                            report.conflicts.len()
+                        ));
+                    }
+                    return Ok(());
+                }
+                let template = args.get_one::<String>("template").unwrap();
+                let dest = PathBuf::from(args.get_one::<String>("name").unwrap());
+                let scaffold = template::init_project(template, &dest, &options)?;
+                println!(
+                    "created {} with {} files",
+                    dest.display(),
+                    scaffold.files.len()
+                );
+                for action in scaffold.actions {
+                    println!("ran {}", action);
+                }
+                Ok(())
+            }
             "xf" => {
                 let filename = args.get_one::<String>("filename");
                 let directory = args.get_one::<String>("directory");
@@ -388,7 +814,14 @@ Commands:
                             let patch = next_patch(tag_version.clone()).to_string();
                             let minor = next_minor(tag_version.clone()).to_string();
                             let major = next_major(tag_version.clone()).to_string();
-                            println!("Current tag: {}", tag);
+                            let rev = util::git_rev_of_tag(&format!("{}^{{commit}}", tag))?;
+                            println!("Current tag: {} ({})", tag, rev);
+                            // the next tag goes on HEAD, which may be past the current one
+                            if let Some(head) = util::git_head_hash() {
+                                if !head.starts_with(&rev) {
+                                    println!("Head commit: {}", &head[..rev.len()]);
+                                }
+                            }
                             println!("Next  phase: {}", phase);
                             println!("Next    pre: {}", pre);
                             println!("Next  patch: {}", patch);
@@ -410,3 +843,532 @@ Commands:
         },
     }
 }
+
+fn cli() -> Command {
+    command!()
+        .help_template(
+            "{about}
+{author}
+
+Commands:
+{subcommands}",
+        )
+        .subcommand(
+            Command::new("install")
+                .about("Install/add/i plugins")
+                .aliases(["add", "i"])
+                .arg(Arg::new("plugin").help(
+                    "plugin[@version], such as 'node' or 'node@20.1.0', default all pinned in .cot-versions",
+                ))
+                .arg(
+                    Arg::new("source")
+                        .long("source")
+                        .help("plugin index, or directory/file:// url of plugin archives, default $COT_SOURCE"),
+                )
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .short('f')
+                        .action(ArgAction::SetTrue)
+                        .help("reinstall even if already installed"),
+                )
+                .arg(
+                    Arg::new("offline")
+                        .long("offline")
+                        .action(ArgAction::SetTrue)
+                        .help("never download, install from the download cache, --mirror or a local source"),
+                )
+                .arg(
+                    Arg::new("mirror")
+                        .long("mirror")
+                        .help("directory of plugin archives looked at before the source"),
+                )
+                .arg(
+                    Arg::new("locked")
+                        .long("locked")
+                        .action(ArgAction::SetTrue)
+                        .help("install exactly what .cot-versions.lock records, never resolve versions"),
+                ),
+        )
+        .subcommand(
+            Command::new("lock")
+                .about("Record the exact version, url and sha256 of every pin in .cot-versions.lock")
+                .arg(
+                    Arg::new("source")
+                        .long("source")
+                        .help("plugin index, or directory/file:// url of plugin archives, default $COT_SOURCE"),
+                )
+                .arg(
+                    Arg::new("offline")
+                        .long("offline")
+                        .action(ArgAction::SetTrue)
+                        .help("never download, checksum archives from the download cache, --mirror or a local source"),
+                )
+                .arg(
+                    Arg::new("mirror")
+                        .long("mirror")
+                        .help("directory of plugin archives looked at before the source"),
+                ),
+        )
+        .subcommand(
+            Command::new("remove")
+                .about("Remove/delete/del/rm plugin")
+                .aliases(["rm", "delete", "del"])
+                .arg(
+                    Arg::new("plugin")
+                        .required(true)
+                        .help("plugin[@version], such as 'node@20.1.0'")
+                        .add(ArgValueCandidates::new(plugin_candidates)),
+                )
+                .arg(
+                    Arg::new("all")
+                        .long("all")
+                        .action(ArgAction::SetTrue)
+                        .help("remove all installed versions"),
+                )
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .short('f')
+                        .action(ArgAction::SetTrue)
+                        .help("remove even if active or needed by another plugin"),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .short('n')
+                        .action(ArgAction::SetTrue)
+                        .help("only print what would be deleted"),
+                ),
+        )
+        .subcommand(
+            Command::new("use")
+                .about("Use/active plugin")
+                .aliases(["active"])
+                .arg(
+                    Arg::new("plugin")
+                        .required(true)
+                        .help("plugin[@version], such as 'node@20.1.0'")
+                        .add(ArgValueCandidates::new(plugin_candidates)),
+                )
+                .arg(
+                    Arg::new("local")
+                        .long("local")
+                        .short('l')
+                        .action(ArgAction::SetTrue)
+                        .help("pin the version in .cot-versions of current directory"),
+                ),
+        )
+        .subcommand(
+            Command::new("current")
+                .about("Current plugin versions and the .cot-versions selecting them")
+                .arg(
+                    Arg::new("plugin")
+                        .help("only this plugin")
+                        .add(ArgValueCandidates::new(plugin_candidates)),
+                ),
+        )
+        .subcommand(
+            Command::new("upgrade")
+                .about("Upgrade/up installed plugins to the newest versions of the source")
+                .aliases(["up"])
+                .arg(
+                    Arg::new("plugin")
+                        .help("plugin[@version] to upgrade within, such as 'node@20', default all installed")
+                        .add(ArgValueCandidates::new(plugin_candidates)),
+                )
+                .arg(
+                    Arg::new("use")
+                        .long("use")
+                        .action(ArgAction::SetTrue)
+                        .help("make the upgraded versions the active ones"),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .short('n')
+                        .action(ArgAction::SetTrue)
+                        .help("only print what would be upgraded"),
+                )
+                .arg(
+                    Arg::new("source")
+                        .long("source")
+                        .help("plugin index, or directory/file:// url of plugin archives, default $COT_SOURCE"),
+                )
+                .arg(
+                    Arg::new("offline")
+                        .long("offline")
+                        .action(ArgAction::SetTrue)
+                        .help("never download, install from the download cache, --mirror or a local source"),
+                )
+                .arg(
+                    Arg::new("mirror")
+                        .long("mirror")
+                        .help("directory of plugin archives looked at before the source"),
+                ),
+        )
+        .subcommand(
+            Command::new("outdated")
+                .about("Show installed and pinned plugins behind the source")
+                .arg(
+                    Arg::new("source")
+                        .long("source")
+                        .help("plugin index, or directory/file:// url of plugin archives, default $COT_SOURCE"),
+                )
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .action(ArgAction::SetTrue)
+                        .help("print as json"),
+                ),
+        )
+        .subcommand(
+            Command::new("self-update")
+                .about("Replace cot with the one of a release archive")
+                .arg(
+                    Arg::new("release")
+                        .required(true)
+                        .help("path or file:// url of a cot release archive or binary"),
+                )
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .short('f')
+                        .action(ArgAction::SetTrue)
+                        .help("replace even with the same or an older version"),
+                ),
+        )
+        .subcommand(
+            Command::new("gc")
+                .about("Remove plugin versions no project uses and prune the download cache")
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .short('n')
+                        .action(ArgAction::SetTrue)
+                        .help("only print what would be removed"),
+                )
+                .arg(
+                    Arg::new("max-age")
+                        .long("max-age")
+                        .value_parser(clap::value_parser!(u64))
+                        .default_value("30")
+                        .help("days to keep downloaded archives since they were last used"),
+                )
+                .arg(
+                    Arg::new("max-size")
+                        .long("max-size")
+                        .help("size the download cache is pruned to, such as 500M or 2G"),
+                ),
+        )
+        .subcommand(
+            Command::new("reshim").about("Rebuild shims of plugin executables in $COT_HOME/shims"),
+        )
+        .subcommand(
+            Command::new("exec")
+                .about("Run a command with a plugin version's bin dirs first in PATH")
+                .arg(
+                    Arg::new("plugin")
+                        .required(true)
+                        .help("plugin@version, such as 'node@20', need not be the active one")
+                        .add(ArgValueCandidates::new(plugin_candidates)),
+                )
+                .arg(Arg::new("command").required(true).help("command to run"))
+                .arg(
+                    Arg::new("args")
+                        .num_args(0..)
+                        .trailing_var_arg(true)
+                        .allow_hyphen_values(true),
+                ),
+        )
+        .subcommand(
+            Command::new("env")
+                .about("Print the PATH and env of the active plugins, for eval in a shell")
+                .arg(
+                    Arg::new("shell")
+                        .long("shell")
+                        .short('s')
+                        .value_parser(plugin::SHELLS)
+                        .help("shell to print for, default from $SHELL"),
+                )
+                .arg(
+                    Arg::new("hook")
+                        .long("hook")
+                        .action(ArgAction::SetTrue)
+                        .help("only the vars, unsetting those of the last hook, as run by shell-init"),
+                ),
+        )
+        .subcommand(
+            Command::new("shell-init")
+                .about("Print the init code that sets cot up in a shell")
+                .arg(
+                    Arg::new("shell")
+                        .required(true)
+                        .value_parser(plugin::SHELLS),
+                ),
+        )
+        .subcommand(
+            Command::new("completions")
+                .about("Print the completion script of a shell, shell-init loads it")
+                .arg(
+                    Arg::new("shell")
+                        .required(true)
+                        .value_parser(plugin::SHELLS),
+                ),
+        )
+        .subcommand(
+            Command::new("which")
+                .about("Show the plugin executable a shim runs here")
+                .arg(Arg::new("binary").required(true))
+                .arg(
+                    Arg::new("path")
+                        .long("path")
+                        .short('p')
+                        .action(ArgAction::SetTrue)
+                        .help("print only the path of the executable"),
+                ),
+        )
+        .subcommand(
+            Command::new("shim")
+                .about("Run <binary> of the active plugin, used by shims")
+                .hide(true)
+                .arg(Arg::new("binary").required(true))
+                .arg(
+                    Arg::new("args")
+                        .num_args(0..)
+                        .trailing_var_arg(true)
+                        .allow_hyphen_values(true),
+                ),
+        )
+        .subcommand(
+            Command::new("list")
+                .about("List/ls plugins")
+                .aliases(["ls"])
+                .arg(
+                    Arg::new("available")
+                        .long("available")
+                        .short('a')
+                        .action(ArgAction::SetTrue)
+                        .help("list plugins offered by the source instead of installed ones"),
+                )
+                .arg(
+                    Arg::new("source")
+                        .long("source")
+                        .help("plugin index, or directory/file:// url of plugin archives, default $COT_SOURCE"),
+                )
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .action(ArgAction::SetTrue)
+                        .help("print as json"),
+                ),
+        )
+        .subcommand(
+            Command::new("init")
+                .about("Init/create new project from a template")
+                .aliases(["create"])
+                .arg(
+                    Arg::new("template")
+                        .required_unless_present_any(["list", "update"])
+                        .help("built-in template, template directory, .tar.gz/.tgz/.tar/.zip archive, or git repository[#dir]"),
+                )
+                .arg(
+                    Arg::new("name")
+                        .required_unless_present_any(["list", "update"])
+                        .help("directory to create, its name fills {{name}}"),
+                )
+                .arg(
+                    Arg::new("no-actions")
+                        .long("no-actions")
+                        .action(ArgAction::SetTrue)
+                        .help("only write the files, skip the template's actions such as git init"),
+                )
+                .arg(
+                    Arg::new("list")
+                        .long("list")
+                        .short('l')
+                        .action(ArgAction::SetTrue)
+                        .help("list the built-in templates"),
+                )
+                .arg(
+                    Arg::new("update")
+                        .long("update")
+                        .short('u')
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["template", "name", "list"])
+                        .help("merge the changes of the project's template since it was created, run in the project"),
+                )
+                .arg(
+                    Arg::new("set")
+                        .long("set")
+                        .action(ArgAction::Append)
+                        .value_name("KEY=VALUE")
+                        .help("fill {{KEY}} with VALUE, otherwise it comes from $KEY or a prompt"),
+                ),
+        )
+        .subcommand(
+            Command::new("huggingface")
+                .about("Huggingface/hf directory")
+                .aliases(["hf"])
+                .subcommand(
+                    Command::new("ls")
+                        .about("List the models, datasets and spaces in the hub cache")
+                        .aliases(["list"])
+                        .arg(
+                            Arg::new("type")
+                                .long("type")
+                                .short('t')
+                                .value_parser(util::HF_REPO_TYPES)
+                                .help("only list repos of this type"),
+                        )
+                        .arg(
+                            Arg::new("json")
+                                .long("json")
+                                .action(ArgAction::SetTrue)
+                                .help("print as json"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("du")
+                        .about("Disk usage of the hub cache, counting blobs shared by revisions once")
+                        .aliases(["size"])
+                        .arg(
+                            Arg::new("revisions")
+                                .long("revisions")
+                                .short('r')
+                                .action(ArgAction::SetTrue)
+                                .help("also show each revision and how much of it other revisions share"),
+                        )
+                        .arg(
+                            Arg::new("json")
+                                .long("json")
+                                .action(ArgAction::SetTrue)
+                                .help("print as json"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("huggingface-datasets")
+                .about("Huggingface-datasets/hd <id>'s directory")
+                .aliases(["hd"])
+                .arg(
+                    Arg::new("id")
+                        .help("datasets id, such as 'sentence-transformers/all-nli'")
+                        .add(ArgValueCandidates::new(|| hf_candidates("datasets"))),
+                ),
+        )
+        .subcommand(
+            Command::new("huggingface-models")
+                .about("Huggingface-models/hm <id>'s directory")
+                .aliases(["hm"])
+                .arg(
+                    Arg::new("id")
+                        .help("model id, such as 'baai/bge-large-zh-v1.5'")
+                        .add(ArgValueCandidates::new(|| hf_candidates("models"))),
+                ),
+        )
+        .subcommand(
+            Command::new("xf")
+                .about("Extract <filename.tar.gz>")
+                .arg(
+                    Arg::new("directory")
+                        .short('C')
+                        .long("Extract <filename.tar.gz> into <directory>")
+                        .default_value("."),
+                )
+                .arg(Arg::new("filename")),
+        )
+        .subcommand(
+            Command::new("unzip")
+                .about("Extract <filename.zip>")
+                .arg(
+                    Arg::new("directory")
+                        .short('d')
+                        .long("Extract <filename.zip> into <directory>")
+                        .default_value("."),
+                )
+                .arg(Arg::new("filename")),
+        )
+        .subcommand(
+            Command::new("tag")
+                .about("Tag [current|next|date|hash|show]")
+                .aliases(["t"])
+                .subcommand(Command::new("current").about("Current tag").aliases(["c"]))
+                .subcommand(
+                    Command::new("next")
+                        .about("Next [major|minor|patch|pre|phase] of current tag")
+                        .aliases(["n"])
+                        .subcommand(
+                            Command::new("major")
+                                .about("Next major version of current tag")
+                                .aliases(["a"]),
+                        )
+                        .subcommand(
+                            Command::new("minor")
+                                .about("Next minor version of current tag")
+                                .aliases(["i"]),
+                        )
+                        .subcommand(
+                            Command::new("patch")
+                                .about("Next patch version of current tag")
+                                .aliases(["p"]),
+                        )
+                        .subcommand(
+                            Command::new("phase")
+                                .about("Next phase version of current tag")
+                                .aliases(["s"]),
+                        )
+                        .subcommand(
+                            Command::new("pre")
+                                .about("Next pre version of current tag")
+                                .aliases(["r"]),
+                        ),
+                )
+                .subcommand(
+                    Command::new("date")
+                        .about("Date of current tag")
+                        .aliases(["d"]),
+                )
+                .subcommand(
+                    Command::new("hash")
+                        .about("Hash of current tag")
+                        .aliases(["h"]),
+                )
+                .subcommand(
+                    Command::new("show")
+                        .about("Show information about tag")
+                        .aliases(["s"]),
+                ),
+        )
+}
+
+// installed plugins for completing plugin[@version] args
+fn plugin_candidates() -> Vec<CompletionCandidate> {
+    let specs = plugin::cot_home().and_then(|home| plugin::installed_specs(&home));
+    specs
+        .unwrap_or_default()
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+// models or datasets in the hub cache
+fn hf_candidates(kind: &str) -> Vec<CompletionCandidate> {
+    let ids = util::huggingface_hub_cache().and_then(|cache| util::hf_cached_ids(&cache, kind));
+    ids.unwrap_or_default()
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+// install options from --offline and --mirror of install and upgrade
+fn install_options(home: &Path, args: &ArgMatches, force: bool) -> plugin::InstallOptions {
+    plugin::InstallOptions {
+        force,
+        fetch: plugin::FetchOptions {
+            cache: Some(plugin::download_cache(home)),
+            mirror: args.get_one::<String>("mirror").map(PathBuf::from),
+            offline: args.get_flag("offline"),
+        },
+    }
+}
@@ -0,0 +1,297 @@
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use semver::{Version, VersionReq};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::home::{plugin_dir, version_dir};
+use super::manifest::InstallManifest;
+use super::pins::{find_pin_file, write_pin, PinFile, PIN_FILE};
+use super::projects::record_project;
+use super::spec::PluginSpec;
+
+pub const CURRENT: &str = "current";
+
+/// Where the active version of a plugin was selected.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Origin {
+    Project(PathBuf),
+    Global,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Active {
+    pub name: String,
+    pub version: Version,
+    pub origin: Origin,
+}
+
+pub fn current_link(home: &Path, name: &str) -> PathBuf {
+    plugin_dir(home, name).join(CURRENT)
+}
+
+// highest installed version matching req
+pub fn installed_match(home: &Path, name: &str, req: &VersionReq) -> Result<Option<Version>> {
+    let manifest = InstallManifest::load(home)?;
+    Ok(manifest
+        .versions(name)
+        .iter()
+        .map(|v| &v.version)
+        .filter(|v| req.matches(v) && version_dir(home, name, &v.to_string()).is_dir())
+        .max()
+        .cloned())
+}
+
+// point $COT_HOME/plugins/<name>/current at <version>, replacing it atomically
+pub fn set_global(home: &Path, name: &str, version: &Version) -> Result<()> {
+    let target = version_dir(home, name, &version.to_string());
+    if !target.is_dir() {
+        return Err(anyhow!("{}@{} is not installed", name, version));
+    }
+    let link = current_link(home, name);
+    let temp = plugin_dir(home, name).join(format!(".{}.tmp", CURRENT));
+    if temp.symlink_metadata().is_ok() {
+        fs::remove_file(&temp)?;
+    }
+    make_link(&version.to_string(), &temp)?;
+    fs::rename(&temp, &link).with_context(|| format!("Failed to update {}", link.display()))?;
+    debug!("{} -> {}", link.display(), version);
+    Ok(())
+}
+
+// `cot use`: select the highest installed version matching spec, globally,
+// or for the project in dir by writing its .cot-versions
+pub fn use_version(home: &Path, spec: &PluginSpec, project: Option<&Path>) -> Result<Active> {
+    let version = installed_match(home, &spec.name, &spec.version_req()?)?.ok_or_else(|| {
+        anyhow!(
+            "no installed version of {} matches, run `cot install {}` first",
+            spec,
+            spec
+        )
+    })?;
+    let origin = match project {
+        Some(dir) => {
+            let path = dir.join(PIN_FILE);
+            let pin = PluginSpec {
+                name: spec.name.clone(),
+                version: Some(spec.version.clone().unwrap_or(version.to_string())),
+            };
+            write_pin(&path, &pin)?;
+            record_project(home, &path)?;
+            Origin::Project(path)
+        }
+        None => {
+            set_global(home, &spec.name, &version)?;
+            Origin::Global
+        }
+    };
+    Ok(Active {
+        name: spec.name.clone(),
+        version,
+        origin,
+    })
+}
+
+#[cfg(unix)]
+fn make_link(version: &str, link: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(version, link)
+        .with_context(|| format!("Failed to create {}", link.display()))
+}
+
+// symlinks need extra privileges on windows, keep the version in a plain file instead
+#[cfg(not(unix))]
+fn make_link(version: &str, link: &Path) -> Result<()> {
+    fs::write(link, version).with_context(|| format!("Failed to create {}", link.display()))
+}
+
+pub fn global_version(home: &Path, name: &str) -> Option<Version> {
+    let link = current_link(home, name);
+    let metadata = link.symlink_metadata().ok()?;
+    let version = if metadata.file_type().is_symlink() {
+        fs::read_link(&link)
+            .ok()?
+            .file_name()?
+            .to_string_lossy()
+            .to_string()
+    } else {
+        fs::read_to_string(&link).ok()?.trim().to_string()
+    };
+    let version = Version::parse(&version).ok()?;
+    if version_dir(home, name, &version.to_string()).is_dir() {
+        Some(version)
+    } else {
+        None
+    }
+}
+
+// a project pin found from dir upwards wins over the global selection
+pub fn active_version(home: &Path, name: &str, dir: &Path) -> Result<Option<Active>> {
+    if let Some(path) = find_pin_file(dir) {
+        let pins = PinFile::load(&path)?;
+        if let Some(pin) = pins.get(name) {
+            let version = installed_match(home, name, &pin.version_req()?)?
+                .ok_or_else(|| anyhow!("{} pinned in {} is not installed", pin, path.display()))?;
+            return Ok(Some(Active {
+                name: name.to_string(),
+                version,
+                origin: Origin::Project(path),
+            }));
+        }
+    }
+    Ok(global_version(home, name).map(|version| Active {
+        name: name.to_string(),
+        version,
+        origin: Origin::Global,
+    }))
+}
+
+/// What `cot current` reports for one plugin.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Current {
+    pub name: String,
+    // the pinned requirement, none for the global selection
+    pub pinned: Option<String>,
+    // none when nothing installed satisfies the selection
+    pub version: Option<Version>,
+    pub origin: Origin,
+}
+
+// every installed or pinned plugin, with the version that applies in dir and where it comes from
+pub fn current(home: &Path, dir: &Path) -> Result<Vec<Current>> {
+    let manifest = InstallManifest::load(home)?;
+    let pins = match find_pin_file(dir) {
+        Some(path) => Some(PinFile::load(&path)?),
+        None => None,
+    };
+    let mut names: Vec<String> = manifest.plugins.keys().cloned().collect();
+    if let Some(pins) = &pins {
+        names.extend(pins.pins.iter().map(|p| p.name.clone()));
+    }
+    names.sort();
+    names.dedup();
+
+    let mut entries = vec![];
+    for name in names {
+        let pin = pins.as_ref().and_then(|p| p.get(&name).map(|s| (p, s)));
+        let entry = match pin {
+            Some((pins, spec)) => Current {
+                version: installed_match(home, &name, &spec.version_req()?)?,
+                pinned: spec.version.clone(),
+                origin: Origin::Project(pins.path.clone()),
+                name,
+            },
+            None => Current {
+                version: global_version(home, &name),
+                pinned: None,
+                origin: Origin::Global,
+                name,
+            },
+        };
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::install::tests::make_archive;
+    use crate::plugin::install::{install, InstallOptions};
+    use crate::plugin::source::Source;
+
+    #[test]
+    fn test_active_version() {
+        let home = tempfile::tempdir().unwrap();
+        let source_dir = tempfile::tempdir().unwrap();
+        make_archive(source_dir.path(), "hello", "1.0.0");
+        make_archive(source_dir.path(), "hello", "2.0.0");
+        let source = Source::parse(source_dir.path().to_str().unwrap());
+        for spec in ["hello@1.0.0", "hello@2.0.0"] {
+            install(
+                home.path(),
+                &source,
+                &PluginSpec::parse(spec).unwrap(),
+                &InstallOptions::default(),
+            )
+            .unwrap();
+        }
+        let project = tempfile::tempdir().unwrap();
+        assert_eq!(
+            active_version(home.path(), "hello", project.path()).unwrap(),
+            None
+        );
+
+        let v2 = Version::parse("2.0.0").unwrap();
+        set_global(home.path(), "hello", &v2).unwrap();
+        set_global(home.path(), "hello", &v2).unwrap();
+        let active = active_version(home.path(), "hello", project.path())
+            .unwrap()
+            .unwrap();
+        assert_eq!(active.version, v2);
+        assert_eq!(active.origin, Origin::Global);
+
+        let pin_file = project.path().join(PIN_FILE);
+        write_pin(&pin_file, &PluginSpec::parse("hello@^1").unwrap()).unwrap();
+        let active = active_version(home.path(), "hello", project.path())
+            .unwrap()
+            .unwrap();
+        assert_eq!(active.version, Version::parse("1.0.0").unwrap());
+        assert_eq!(active.origin, Origin::Project(pin_file.clone()));
+
+        write_pin(&pin_file, &PluginSpec::parse("hello@3").unwrap()).unwrap();
+        assert!(active_version(home.path(), "hello", project.path()).is_err());
+        assert!(set_global(home.path(), "hello", &Version::parse("3.0.0").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_current() {
+        let home = tempfile::tempdir().unwrap();
+        let source_dir = tempfile::tempdir().unwrap();
+        make_archive(source_dir.path(), "hello", "1.0.0");
+        let source = Source::parse(source_dir.path().to_str().unwrap());
+        let spec = PluginSpec::parse("hello").unwrap();
+        install(home.path(), &source, &spec, &InstallOptions::default()).unwrap();
+        let project = tempfile::tempdir().unwrap();
+        fs::write(project.path().join(PIN_FILE), "world@2\n").unwrap();
+
+        let entries = current(home.path(), project.path()).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                Current {
+                    name: "hello".to_string(),
+                    pinned: None,
+                    version: None,
+                    origin: Origin::Global,
+                },
+                Current {
+                    name: "world".to_string(),
+                    pinned: Some("2".to_string()),
+                    version: None,
+                    origin: Origin::Project(project.path().join(PIN_FILE)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_use_version() {
+        let home = tempfile::tempdir().unwrap();
+        let source_dir = tempfile::tempdir().unwrap();
+        make_archive(source_dir.path(), "hello", "1.0.0");
+        let source = Source::parse(source_dir.path().to_str().unwrap());
+        let spec = PluginSpec::parse("hello").unwrap();
+        assert!(use_version(home.path(), &spec, None).is_err());
+        install(home.path(), &source, &spec, &InstallOptions::default()).unwrap();
+
+        let active = use_version(home.path(), &spec, None).unwrap();
+        assert_eq!(active.origin, Origin::Global);
+        assert_eq!(global_version(home.path(), "hello"), Some(active.version));
+
+        let project = tempfile::tempdir().unwrap();
+        let active = use_version(home.path(), &spec, Some(project.path())).unwrap();
+        let pin_file = project.path().join(PIN_FILE);
+        assert_eq!(active.origin, Origin::Project(pin_file.clone()));
+        assert_eq!(fs::read_to_string(pin_file).unwrap(), "hello@1.0.0\n");
+    }
+}
@@ -0,0 +1,215 @@
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
+
+use super::install::utf8_path;
+use super::source::Artifact;
+use super::verify::check_checksum;
+use crate::util::{sha256_file, xdg_cache_home};
+
+// $XDG_CACHE_HOME/cot/downloads, or $COT_HOME/cache/downloads without a cache home
+pub fn download_cache(home: &Path) -> PathBuf {
+    match xdg_cache_home() {
+        Ok(cache) => PathBuf::from(cache).join("cot").join("downloads"),
+        Err(_) => home.join("cache").join("downloads"),
+    }
+}
+
+// <cache>/<sha256>/<file name>
+pub fn cached_archive(cache: &Path, sha256: &str, file_name: &str) -> PathBuf {
+    cache.join(sha256.to_lowercase()).join(file_name)
+}
+
+/// Where `fetch` may take archives from.
+#[derive(Debug, Clone, Default)]
+pub struct FetchOptions {
+    // download cache, none to use archives where they are
+    pub cache: Option<PathBuf>,
+    // a directory with archives by file name, looked at before the source
+    pub mirror: Option<PathBuf>,
+    // never download, only the cache, the mirror and local sources are used
+    pub offline: bool,
+}
+
+// a local copy of the artifact: from the cache, the mirror, or fetched into the cache
+pub fn fetch(artifact: &Artifact, options: &FetchOptions) -> Result<PathBuf> {
+    let file_name = artifact.file_name();
+    if let (Some(cache), Some(sha256)) = (&options.cache, &artifact.sha256) {
+        let cached = cached_archive(cache, sha256, &file_name);
+        if cached.is_file() {
+            debug!("{} from cache {}", file_name, cached.display());
+            // `cot gc --max-age` goes by when an archive was last used
+            if let Err(e) = touch(&cached) {
+                debug!("Failed to touch {}: {:?}", cached.display(), e);
+            }
+            return Ok(cached);
+        }
+    }
+    let mirrored = options
+        .mirror
+        .as_ref()
+        .map(|m| m.join(&file_name))
+        .filter(|p| p.is_file());
+    let local = mirrored.or_else(|| artifact.local_path());
+    let Some(cache) = &options.cache else {
+        return match local {
+            Some(path) => Ok(path),
+            None if options.offline => Err(missing(artifact, options)),
+            None => Err(anyhow!("no download cache to fetch {} into", artifact.url)),
+        };
+    };
+
+    fs::create_dir_all(cache)?;
+    let temp = tempfile::Builder::new()
+        .prefix(".fetch-")
+        .tempdir_in(cache)?;
+    let download = temp.path().join(&file_name);
+    match local {
+        Some(path) => {
+            fs::copy(&path, &download)
+                .with_context(|| format!("Failed to copy {}", path.display()))?;
+        }
+        None if options.offline => return Err(missing(artifact, options)),
+        None => download_url(&artifact.url, &download)?,
+    }
+    let sha256 = sha256_file(utf8_path(&download)?)?;
+    // a corrupt download shouldn't end up in the cache
+    if let Some(expected) = &artifact.sha256 {
+        check_checksum(&file_name, expected, &sha256)?;
+    }
+    let cached = cached_archive(cache, &sha256, &file_name);
+    fs::create_dir_all(cached.parent().unwrap_or(cache))?;
+    fs::rename(&download, &cached)
+        .with_context(|| format!("Failed to store {}", cached.display()))?;
+    debug!("{} cached as {}", artifact.url, cached.display());
+    Ok(cached)
+}
+
+fn touch(path: &Path) -> std::io::Result<()> {
+    fs::OpenOptions::new()
+        .write(true)
+        .open(path)?
+        .set_modified(SystemTime::now())
+}
+
+fn missing(artifact: &Artifact, options: &FetchOptions) -> anyhow::Error {
+    let mut places = vec![];
+    if let Some(cache) = &options.cache {
+        places.push(format!("download cache {}", cache.display()));
+    }
+    if let Some(mirror) = &options.mirror {
+        places.push(format!("mirror {}", mirror.display()));
+    }
+    anyhow!(
+        "{}@{} ({}, sha256 {}) is not in the {}, offline install is not possible",
+        artifact.name,
+        artifact.version,
+        artifact.file_name(),
+        artifact.sha256.as_deref().unwrap_or("unknown"),
+        if places.is_empty() {
+            "local source".to_string()
+        } else {
+            places.join(" or ")
+        }
+    )
+}
+
+// shell out to curl, like util::git does to git
+fn download_url(url: &str, dest: &Path) -> Result<()> {
+    debug!("downloading {}", url);
+    let output = Command::new("curl")
+        .arg("-fsSL")
+        .arg("-o")
+        .arg(dest)
+        .arg(url)
+        .output()
+        .with_context(|| format!("Failed to run curl for {}", url))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Failed to download {}: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use semver::Version;
+
+    fn artifact(url: &str, sha256: Option<&str>) -> Artifact {
+        Artifact {
+            name: "hello".to_string(),
+            version: Version::parse("1.0.0").unwrap(),
+            url: url.to_string(),
+            sha256: sha256.map(|s| s.to_string()),
+            signature: None,
+            depends: vec![],
+        }
+    }
+
+    #[test]
+    fn test_fetch_into_cache() {
+        let source = tempfile::tempdir().unwrap();
+        let cache = tempfile::tempdir().unwrap();
+        let path = source.path().join("hello-1.0.0.tar.gz");
+        fs::write(&path, "abc").unwrap();
+        let sha256 = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        let options = FetchOptions {
+            cache: Some(cache.path().to_path_buf()),
+            ..FetchOptions::default()
+        };
+        let local = artifact(path.to_str().unwrap(), Some(sha256));
+        let cached = fetch(&local, &options).unwrap();
+        assert_eq!(
+            cached,
+            cached_archive(cache.path(), sha256, "hello-1.0.0.tar.gz")
+        );
+
+        // offline, the source is gone but the cache has it
+        let options = FetchOptions {
+            offline: true,
+            ..options
+        };
+        let remote = artifact("https://example.com/hello-1.0.0.tar.gz", Some(sha256));
+        let long_ago = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        fs::File::options()
+            .write(true)
+            .open(&cached)
+            .unwrap()
+            .set_modified(long_ago)
+            .unwrap();
+        assert_eq!(fetch(&remote, &options).unwrap(), cached);
+        // a cache hit counts as a use
+        assert!(cached.metadata().unwrap().modified().unwrap() > long_ago);
+    }
+
+    #[test]
+    fn test_fetch_offline() {
+        let cache = tempfile::tempdir().unwrap();
+        let mirror = tempfile::tempdir().unwrap();
+        let options = FetchOptions {
+            cache: Some(cache.path().to_path_buf()),
+            mirror: Some(mirror.path().to_path_buf()),
+            offline: true,
+        };
+        let remote = artifact("https://example.com/hello-1.0.0.tar.gz", Some("00"));
+        let e = fetch(&remote, &options).unwrap_err().to_string();
+        assert!(e.contains("hello@1.0.0 (hello-1.0.0.tar.gz, sha256 00)"));
+        assert!(e.contains("mirror"));
+
+        fs::write(mirror.path().join("hello-1.0.0.tar.gz"), "abc").unwrap();
+        let e = fetch(&remote, &options).unwrap_err().to_string();
+        assert!(e.starts_with("checksum mismatch for hello-1.0.0.tar.gz: expected sha256 00"));
+
+        let sha256 = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        let remote = artifact("https://example.com/hello-1.0.0.tar.gz", Some(sha256));
+        let cached = fetch(&remote, &options).unwrap();
+        assert!(cached.starts_with(cache.path()));
+    }
+}
@@ -0,0 +1,154 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const DESCRIPTOR_FILE: &str = "cot-plugin.yaml";
+
+/// Optional `cot-plugin.yaml` at the root of a plugin archive, such as
+///
+/// ```yaml
+/// bin: [bin, jre/bin]
+/// env:
+///   JAVA_HOME: ${PLUGIN_DIR}
+/// hooks:
+///   post-install: chmod +x ${PLUGIN_DIR}/bin/*
+///   pre-remove: rm -f $COT_HOME/etc/java.conf
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PluginDescriptor {
+    // directories holding the executables to put on PATH, relative to the plugin directory
+    #[serde(default = "default_bin")]
+    pub bin: Vec<String>,
+    // set while the plugin is active, values expand ${PLUGIN_DIR} and friends
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub hooks: Hooks,
+}
+
+/// Shell commands run in the plugin directory, see [`super::run_hook`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Hooks {
+    // before the unpacked plugin is moved into place
+    pub pre_install: Option<String>,
+    // after it is in place, before it is recorded as installed
+    pub post_install: Option<String>,
+    pub pre_remove: Option<String>,
+}
+
+fn default_bin() -> Vec<String> {
+    vec!["bin".to_string()]
+}
+
+impl Default for PluginDescriptor {
+    fn default() -> Self {
+        PluginDescriptor {
+            bin: default_bin(),
+            env: BTreeMap::new(),
+            hooks: Hooks::default(),
+        }
+    }
+}
+
+impl PluginDescriptor {
+    pub fn load(dir: &Path) -> Result<PluginDescriptor> {
+        let path = dir.join(DESCRIPTOR_FILE);
+        if !path.is_file() {
+            return Ok(PluginDescriptor::default());
+        }
+        let data = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_yaml::from_str(&data).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn bin_dirs(&self, dir: &Path) -> Vec<PathBuf> {
+        self.bin
+            .iter()
+            .map(|b| dir.join(b))
+            .filter(|d| d.is_dir())
+            .collect()
+    }
+
+    // executable name => path, for every executable in the bin directories
+    pub fn binaries(&self, dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+        let mut binaries = vec![];
+        for bin_dir in self.bin_dirs(dir) {
+            for entry in fs::read_dir(&bin_dir)? {
+                let path = entry?.path();
+                if let Some(name) = executable_name(&path) {
+                    binaries.push((name, path));
+                }
+            }
+        }
+        binaries.sort();
+        Ok(binaries)
+    }
+}
+
+#[cfg(unix)]
+fn executable_name(path: &Path) -> Option<String> {
+    use std::os::unix::fs::PermissionsExt;
+    let metadata = fs::metadata(path).ok()?;
+    if !metadata.is_file() || metadata.permissions().mode() & 0o111 == 0 {
+        return None;
+    }
+    Some(path.file_name()?.to_string_lossy().to_string())
+}
+
+// node.exe => node
+#[cfg(not(unix))]
+fn executable_name(path: &Path) -> Option<String> {
+    if !path.is_file() {
+        return None;
+    }
+    let ext = path.extension()?.to_string_lossy().to_lowercase();
+    if !["exe", "cmd", "bat", "ps1"].contains(&ext.as_str()) {
+        return None;
+    }
+    Some(path.file_stem()?.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_descriptor() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            PluginDescriptor::load(dir.path()).unwrap(),
+            PluginDescriptor::default()
+        );
+        fs::write(
+            dir.path().join(DESCRIPTOR_FILE),
+            "bin: [tools]\nenv:\n  TOOLS_HOME: ${PLUGIN_DIR}\nhooks:\n  post-install: ./setup.sh\n",
+        )
+        .unwrap();
+        let descriptor = PluginDescriptor::load(dir.path()).unwrap();
+        assert_eq!(descriptor.bin, vec!["tools"]);
+        assert_eq!(descriptor.env["TOOLS_HOME"], "${PLUGIN_DIR}");
+        assert_eq!(
+            descriptor.hooks.post_install,
+            Some("./setup.sh".to_string())
+        );
+        assert_eq!(descriptor.hooks.pre_install, None);
+        assert!(descriptor.bin_dirs(dir.path()).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_binaries() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let bin = dir.path().join("bin");
+        fs::create_dir(&bin).unwrap();
+        fs::write(bin.join("tool"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(bin.join("tool"), fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(bin.join("README"), "").unwrap();
+        let binaries = PluginDescriptor::default().binaries(dir.path()).unwrap();
+        assert_eq!(binaries, vec![("tool".to_string(), bin.join("tool"))]);
+    }
+}
@@ -0,0 +1,370 @@
+use anyhow::{anyhow, Result};
+use clap_complete::env::Shells;
+use log::warn;
+use std::env;
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::active::{active_version, Active};
+use super::descriptor::PluginDescriptor;
+use super::home::{shims_dir, version_dir};
+use super::hooks::{plugin_env, plugin_vars};
+use super::manifest::InstallManifest;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Pwsh,
+    Nu,
+}
+
+pub const SHELLS: [&str; 6] = ["bash", "zsh", "fish", "pwsh", "powershell", "nu"];
+
+// names of the vars the last `cot env --hook` set, so the next one can unset them
+pub const ENV_VARS: &str = "COT_ENV_VARS";
+
+impl fmt::Display for Shell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Shell::Bash => write!(f, "bash"),
+            Shell::Zsh => write!(f, "zsh"),
+            Shell::Fish => write!(f, "fish"),
+            Shell::Pwsh => write!(f, "pwsh"),
+            Shell::Nu => write!(f, "nu"),
+        }
+    }
+}
+
+impl Shell {
+    pub fn parse(name: &str) -> Result<Shell> {
+        match name.to_lowercase().as_str() {
+            "bash" | "sh" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            "powershell" | "pwsh" => Ok(Shell::Pwsh),
+            "nu" | "nushell" => Ok(Shell::Nu),
+            _ => Err(anyhow!(
+                "unknown shell {}, use one of {}",
+                name,
+                SHELLS.join(", ")
+            )),
+        }
+    }
+
+    // from $SHELL, powershell on windows, bash otherwise
+    pub fn detect() -> Shell {
+        let from_env = env::var("SHELL").ok().and_then(|shell| {
+            let name = Path::new(&shell).file_stem()?.to_string_lossy().to_string();
+            Shell::parse(&name).ok()
+        });
+        match from_env {
+            Some(shell) => shell,
+            None if cfg!(windows) => Shell::Pwsh,
+            None => Shell::Bash,
+        }
+    }
+
+    pub fn quote(&self, value: &str) -> String {
+        match self {
+            Shell::Bash | Shell::Zsh => format!("'{}'", value.replace('\'', "'\\''")),
+            Shell::Fish => format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'")),
+            Shell::Pwsh => format!("'{}'", value.replace('\'', "''")),
+            // nu single quotes have no escapes, raw strings take the rest
+            Shell::Nu if value.contains('\'') => format!("r#'{}'#", value),
+            Shell::Nu => format!("'{}'", value),
+        }
+    }
+
+    pub fn export(&self, name: &str, value: &str) -> String {
+        match self {
+            Shell::Bash | Shell::Zsh => format!("export {}={}", name, self.quote(value)),
+            Shell::Fish => format!("set -gx {} {}", name, self.quote(value)),
+            Shell::Pwsh => format!("$env:{} = {}", name, self.quote(value)),
+            Shell::Nu => format!("$env.{} = {}", name, self.quote(value)),
+        }
+    }
+
+    pub fn unset(&self, name: &str) -> String {
+        match self {
+            Shell::Bash | Shell::Zsh => format!("unset {}", name),
+            Shell::Fish => format!("set -e {}", name),
+            Shell::Pwsh => format!("Remove-Item Env:{} -ErrorAction SilentlyContinue", name),
+            Shell::Nu => format!("hide-env --ignore-errors {}", name),
+        }
+    }
+
+    // dirs in front of the PATH the shell has
+    pub fn prepend_path(&self, dirs: &[PathBuf]) -> String {
+        let dirs: Vec<String> = dirs
+            .iter()
+            .map(|d| self.quote(&d.to_string_lossy()))
+            .collect();
+        match self {
+            Shell::Bash | Shell::Zsh => format!("export PATH={}:\"$PATH\"", dirs.join(":")),
+            Shell::Fish => format!("set -gx PATH {} $PATH", dirs.join(" ")),
+            Shell::Pwsh => format!(
+                "$env:PATH = {} + [IO.Path]::PathSeparator + $env:PATH",
+                dirs.join(" + [IO.Path]::PathSeparator + ")
+            ),
+            Shell::Nu => format!("$env.PATH = ($env.PATH | prepend [{}])", dirs.join(" ")),
+        }
+    }
+
+    // init script embedded under static/shell/
+    fn init_template(&self) -> &'static str {
+        match self {
+            Shell::Bash => "shell/init.bash",
+            Shell::Zsh => "shell/init.zsh",
+            Shell::Fish => "shell/init.fish",
+            Shell::Pwsh => "shell/init.ps1",
+            Shell::Nu => "shell/init.nu",
+        }
+    }
+}
+
+/// Bin dirs and env of the plugin versions active in a directory.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ActiveEnv {
+    pub plugins: Vec<Active>,
+    pub path: Vec<PathBuf>,
+    pub vars: Vec<(String, String)>,
+}
+
+pub fn active_env(home: &Path, dir: &Path) -> Result<ActiveEnv> {
+    let manifest = InstallManifest::load(home)?;
+    let mut active_env = ActiveEnv::default();
+    for name in manifest.plugins.keys() {
+        let active = match active_version(home, name, dir) {
+            Ok(Some(active)) => active,
+            Ok(None) => continue,
+            // a broken pin shouldn't break every shell started below it
+            Err(e) => {
+                warn!("{:?}", e);
+                continue;
+            }
+        };
+        let plugin_dir = version_dir(home, name, &active.version.to_string());
+        let descriptor = PluginDescriptor::load(&plugin_dir)?;
+        let vars = plugin_vars(home, name, &active.version, &plugin_dir);
+        active_env.path.extend(descriptor.bin_dirs(&plugin_dir));
+        active_env.vars.extend(plugin_env(&descriptor, &vars));
+        active_env.plugins.push(active);
+    }
+    Ok(active_env)
+}
+
+// `cot env`: statements that apply the active env in shell, a record for `load-env` in nu
+pub fn render_env(shell: Shell, active_env: &ActiveEnv) -> String {
+    if shell == Shell::Nu {
+        let mut record = serde_json::Map::new();
+        if !active_env.path.is_empty() {
+            let mut path = active_env.path.clone();
+            path.extend(env::split_paths(&env::var_os("PATH").unwrap_or_default()));
+            record.insert("PATH".to_string(), serde_json::json!(path));
+        }
+        for (name, value) in &active_env.vars {
+            record.insert(name.clone(), serde_json::json!(value));
+        }
+        return format!("{}\n", serde_json::Value::Object(record));
+    }
+    let mut lines = vec![];
+    if !active_env.path.is_empty() {
+        lines.push(shell.prepend_path(&active_env.path));
+    }
+    for (name, value) in &active_env.vars {
+        lines.push(shell.export(name, value));
+    }
+    lines.iter().map(|line| format!("{}\n", line)).collect()
+}
+
+// `cot env --hook`: run by shell-init on every directory change. Only vars change here,
+// the shims already pick the version pinned in the new directory.
+pub fn render_hook(shell: Shell, active_env: &ActiveEnv, previous: &str) -> String {
+    let names: Vec<&str> = active_env
+        .vars
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect();
+    let stale: Vec<&str> = previous
+        .split(':')
+        .filter(|name| !name.is_empty() && !names.contains(name))
+        .collect();
+    if shell == Shell::Nu {
+        let mut set = serde_json::Map::new();
+        for (name, value) in &active_env.vars {
+            set.insert(name.clone(), serde_json::json!(value));
+        }
+        set.insert(ENV_VARS.to_string(), serde_json::json!(names.join(":")));
+        let hook = serde_json::json!({ "set": set, "unset": stale });
+        return format!("{}\n", hook);
+    }
+    let mut lines: Vec<String> = stale.iter().map(|name| shell.unset(name)).collect();
+    for (name, value) in &active_env.vars {
+        lines.push(shell.export(name, value));
+    }
+    lines.push(shell.export(ENV_VARS, &names.join(":")));
+    lines.iter().map(|line| format!("{}\n", line)).collect()
+}
+
+// var the completion scripts set when they ask cot itself for candidates
+pub const COMPLETE_VAR: &str = "COMPLETE";
+
+// `cot completions`: scripts that call back into cot, so plugin names and hf ids come from
+// what is installed and cached right now. nu gets static completions of the commands only.
+pub fn write_completions(
+    shell: Shell,
+    cmd: &mut clap::Command,
+    cot: &Path,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let name = match shell {
+        Shell::Nu => {
+            clap_complete::generate(clap_complete_nushell::Nushell, cmd, "cot", out);
+            return Ok(());
+        }
+        Shell::Pwsh => "powershell".to_string(),
+        _ => shell.to_string(),
+    };
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(&name)
+        .ok_or_else(|| anyhow!("no completions for {}", shell))?;
+    completer.write_registration(COMPLETE_VAR, "cot", "cot", &cot.to_string_lossy(), out)?;
+    Ok(())
+}
+
+// `cot shell-init`: the embedded init script with this cot and COT_HOME filled in
+pub fn shell_init(shell: Shell, home: &Path, cot: &Path) -> Result<String> {
+    let template = crate::Asset::get(shell.init_template())
+        .ok_or_else(|| anyhow!("no shell init for {}", shell))?;
+    let template = String::from_utf8_lossy(template.data.as_ref());
+    Ok(template
+        .replace("{{cot}}", &shell.quote(&cot.to_string_lossy()))
+        .replace("{{cot_home}}", &shell.quote(&home.to_string_lossy()))
+        .replace(
+            "{{shims}}",
+            &shell.quote(&shims_dir(home).to_string_lossy()),
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_env() {
+        let active_env = ActiveEnv {
+            plugins: vec![],
+            path: vec![PathBuf::from("/opt/java/bin")],
+            vars: vec![("JAVA_HOME".to_string(), "/opt/it's java".to_string())],
+        };
+        assert_eq!(
+            render_env(Shell::Bash, &active_env),
+            "export PATH='/opt/java/bin':\"$PATH\"\nexport JAVA_HOME='/opt/it'\\''s java'\n"
+        );
+        assert_eq!(
+            render_env(Shell::Fish, &active_env),
+            "set -gx PATH '/opt/java/bin' $PATH\nset -gx JAVA_HOME '/opt/it\\'s java'\n"
+        );
+        assert_eq!(
+            render_env(Shell::Pwsh, &active_env),
+            "$env:PATH = '/opt/java/bin' + [IO.Path]::PathSeparator + $env:PATH\n$env:JAVA_HOME = '/opt/it''s java'\n"
+        );
+        assert_eq!(
+            render_env(Shell::Nu, &ActiveEnv::default()),
+            "{}\n".to_string()
+        );
+        assert_eq!(Shell::parse("pwsh").unwrap(), Shell::Pwsh);
+        assert_eq!(Shell::Nu.quote("it's"), "r#'it's'#");
+        assert!(Shell::parse("tcsh").is_err());
+    }
+
+    #[test]
+    fn test_render_hook() {
+        let active_env = ActiveEnv {
+            plugins: vec![],
+            path: vec![PathBuf::from("/opt/java/bin")],
+            vars: vec![("JAVA_HOME".to_string(), "/opt/java".to_string())],
+        };
+        assert_eq!(
+            render_hook(Shell::Bash, &active_env, "GOROOT:JAVA_HOME"),
+            "unset GOROOT\nexport JAVA_HOME='/opt/java'\nexport COT_ENV_VARS='JAVA_HOME'\n"
+        );
+        assert_eq!(
+            render_hook(Shell::Fish, &ActiveEnv::default(), "JAVA_HOME"),
+            "set -e JAVA_HOME\nset -gx COT_ENV_VARS ''\n"
+        );
+        let hook: serde_json::Value =
+            serde_json::from_str(&render_hook(Shell::Nu, &active_env, "GOROOT")).unwrap();
+        assert_eq!(hook["set"]["JAVA_HOME"], "/opt/java");
+        assert_eq!(hook["unset"], serde_json::json!(["GOROOT"]));
+    }
+
+    #[test]
+    fn test_shell_init() {
+        let home = PathBuf::from("/opt/cot");
+        let cot = PathBuf::from("/usr/local/bin/cot");
+        for name in SHELLS {
+            let shell = Shell::parse(name).unwrap();
+            let init = shell_init(shell, &home, &cot).unwrap();
+            assert!(!init.contains("{{"), "{}", init);
+            assert!(init.contains(&shell.quote("/opt/cot/shims")), "{}", init);
+            assert!(init.contains("env --shell"), "{}", init);
+        }
+    }
+
+    #[test]
+    fn test_write_completions() {
+        let mut cmd = clap::Command::new("cot").subcommand(clap::Command::new("install"));
+        let cot = PathBuf::from("/usr/local/bin/cot");
+        for name in SHELLS {
+            let shell = Shell::parse(name).unwrap();
+            let mut out = vec![];
+            write_completions(shell, &mut cmd, &cot, &mut out).unwrap();
+            let script = String::from_utf8(out).unwrap();
+            match shell {
+                Shell::Nu => assert!(script.contains("install"), "{}", script),
+                _ => assert!(script.contains("/usr/local/bin/cot"), "{}", script),
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_active_env() {
+        use crate::plugin::active::set_global;
+        use crate::plugin::install::tests::make_archive_with;
+        use crate::plugin::install::{install, InstallOptions};
+        use crate::plugin::source::Source;
+        use crate::plugin::spec::PluginSpec;
+        use semver::Version;
+
+        let home = tempfile::tempdir().unwrap();
+        let source_dir = tempfile::tempdir().unwrap();
+        let source = Source::parse(source_dir.path().to_str().unwrap());
+        make_archive_with(
+            source_dir.path(),
+            "java",
+            "21.0.0",
+            Some("env:\n  JAVA_HOME: ${PLUGIN_DIR}\n"),
+        );
+        let spec = PluginSpec::parse("java@21.0.0").unwrap();
+        install(home.path(), &source, &spec, &InstallOptions::default()).unwrap();
+        assert!(active_env(home.path(), home.path())
+            .unwrap()
+            .vars
+            .is_empty());
+
+        set_global(home.path(), "java", &Version::parse("21.0.0").unwrap()).unwrap();
+        let dir = version_dir(home.path(), "java", "21.0.0");
+        let active_env = active_env(home.path(), home.path()).unwrap();
+        assert_eq!(active_env.path, vec![dir.join("bin")]);
+        assert_eq!(
+            active_env.vars,
+            vec![("JAVA_HOME".to_string(), dir.to_string_lossy().to_string())]
+        );
+    }
+}
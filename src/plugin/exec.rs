@@ -0,0 +1,102 @@
+use anyhow::{anyhow, Result};
+use log::debug;
+use semver::Version;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::active::installed_match;
+use super::descriptor::PluginDescriptor;
+use super::home::version_dir;
+use super::hooks::{plugin_env, plugin_vars};
+use super::shim::{exec, path_with};
+use super::spec::PluginSpec;
+
+/// An installed plugin version prepared for `cot exec`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecTarget {
+    pub name: String,
+    pub version: Version,
+    pub dir: PathBuf,
+    pub bin_dirs: Vec<PathBuf>,
+    pub env: Vec<(String, String)>,
+    // the program itself, from the plugin when it has one by that name
+    pub program: PathBuf,
+}
+
+// highest installed version matching spec, and where program comes from
+pub fn exec_target(home: &Path, spec: &PluginSpec, program: &str) -> Result<ExecTarget> {
+    let version = installed_match(home, &spec.name, &spec.version_req()?)?.ok_or_else(|| {
+        anyhow!(
+            "no installed version of {} matches, run `cot install {}` first",
+            spec,
+            spec
+        )
+    })?;
+    let dir = version_dir(home, &spec.name, &version.to_string());
+    let descriptor = PluginDescriptor::load(&dir)?;
+    let program = descriptor
+        .binaries(&dir)?
+        .into_iter()
+        .find(|(name, _)| name == program)
+        .map(|(_, path)| path)
+        .unwrap_or(PathBuf::from(program));
+    let vars = plugin_vars(home, &spec.name, &version, &dir);
+    Ok(ExecTarget {
+        env: plugin_env(&descriptor, &vars),
+        name: spec.name.clone(),
+        version,
+        bin_dirs: descriptor.bin_dirs(&dir),
+        dir,
+        program,
+    })
+}
+
+// `cot exec <plugin>@<version> -- <program> <args>`, regardless of the active version
+pub fn exec_version(home: &Path, spec: &PluginSpec, program: &str, args: &[String]) -> Result<()> {
+    let target = exec_target(home, spec, program)?;
+    debug!(
+        "exec {} with {}@{} {}",
+        target.program.display(),
+        target.name,
+        target.version,
+        target.dir.display()
+    );
+    let mut command = Command::new(&target.program);
+    command
+        .args(args)
+        .envs(target.env)
+        .env("PATH", path_with(&target.bin_dirs)?);
+    exec(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::install::tests::make_archive;
+    use crate::plugin::install::{install, InstallOptions};
+    use crate::plugin::source::Source;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exec_target() {
+        let home = tempfile::tempdir().unwrap();
+        let source_dir = tempfile::tempdir().unwrap();
+        let source = Source::parse(source_dir.path().to_str().unwrap());
+        for version in ["1.0.0", "2.0.0"] {
+            make_archive(source_dir.path(), "hello", version);
+            let spec = PluginSpec::parse(&format!("hello@{}", version)).unwrap();
+            install(home.path(), &source, &spec, &InstallOptions::default()).unwrap();
+        }
+        let spec = PluginSpec::parse("hello@1").unwrap();
+        let target = exec_target(home.path(), &spec, "hello").unwrap();
+        let dir = version_dir(home.path(), "hello", "1.0.0");
+        assert_eq!(target.program, dir.join("bin").join("hello"));
+        assert_eq!(target.bin_dirs, vec![dir.join("bin")]);
+        // other programs are left to PATH, which starts with the plugin bin dirs
+        let target = exec_target(home.path(), &spec, "sh").unwrap();
+        assert_eq!(target.program, PathBuf::from("sh"));
+
+        let missing = PluginSpec::parse("hello@3").unwrap();
+        assert!(exec_target(home.path(), &missing, "hello").is_err());
+    }
+}
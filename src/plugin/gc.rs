@@ -0,0 +1,288 @@
+use anyhow::Result;
+use log::{debug, warn};
+use semver::Version;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use super::active::{global_version, installed_match};
+use super::home::version_dir;
+use super::lock::{lock_path, LockFile};
+use super::manifest::InstallManifest;
+use super::pins::{find_pin_file, PinFile};
+use super::projects::{record_project, SeenProjects};
+use super::remove::{plan_removal, remove};
+use super::spec::PluginSpec;
+use crate::util::dir_size;
+
+#[derive(Debug, Clone, Default)]
+pub struct GcOptions {
+    // only report what would be removed
+    pub dry_run: bool,
+    // download cache to prune, none to leave it alone
+    pub cache: Option<PathBuf>,
+    // cached archives older than this go
+    pub max_age: Option<Duration>,
+    // then the oldest ones, until the cache fits
+    pub max_size: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+pub struct GcReport {
+    pub versions: Vec<(String, Version, u64)>,
+    pub archives: Vec<(PathBuf, u64)>,
+    // seen .cot-versions files that no longer exist
+    pub forgotten: Vec<PathBuf>,
+}
+
+impl GcReport {
+    pub fn reclaimed(&self) -> u64 {
+        let versions: u64 = self.versions.iter().map(|(_, _, size)| size).sum();
+        let archives: u64 = self.archives.iter().map(|(_, size)| size).sum();
+        versions + archives
+    }
+}
+
+// `cot gc`: remove versions nothing uses, and prune the download cache
+pub fn gc(home: &Path, dir: &Path, options: &GcOptions) -> Result<GcReport> {
+    // the project gc runs in counts as seen too
+    if let Some(pin_file) = find_pin_file(dir) {
+        if !options.dry_run {
+            record_project(home, &pin_file)?;
+        }
+    }
+    let (kept, forgotten) = kept_versions(home, dir)?;
+    if !options.dry_run && !forgotten.is_empty() {
+        let mut projects = SeenProjects::load(home)?;
+        projects.pin_files.retain(|p| !forgotten.contains(p));
+        projects.save(home)?;
+    }
+
+    let mut report = GcReport {
+        forgotten,
+        ..GcReport::default()
+    };
+    let manifest = InstallManifest::load(home)?;
+    for (name, versions) in &manifest.plugins {
+        for installed in versions {
+            let version = &installed.version;
+            if kept.get(name).is_some_and(|k| k.contains(version)) {
+                continue;
+            }
+            let spec = PluginSpec {
+                name: name.clone(),
+                version: Some(version.to_string()),
+            };
+            // plan_removal still refuses what other plugins depend on
+            let plan = match plan_removal(home, &spec, false, false, home) {
+                Ok(plan) => plan,
+                Err(e) => {
+                    debug!("keep {}@{}: {}", name, version, e);
+                    continue;
+                }
+            };
+            let size = dir_size(&version_dir(home, name, &version.to_string()));
+            if !options.dry_run {
+                if let Err(e) = remove(home, &plan) {
+                    warn!("keep {}@{}: {:?}", name, version, e);
+                    continue;
+                }
+            }
+            report.versions.push((name.clone(), version.clone(), size));
+        }
+    }
+
+    if let Some(cache) = &options.cache {
+        report.archives = prune_cache(cache, options.max_age, options.max_size, options.dry_run)?;
+    }
+    Ok(report)
+}
+
+type Kept = BTreeMap<String, BTreeSet<Version>>;
+
+// global versions, and what the seen projects pin or lock, with the seen pin files that are gone
+fn kept_versions(home: &Path, dir: &Path) -> Result<(Kept, Vec<PathBuf>)> {
+    let manifest = InstallManifest::load(home)?;
+    let mut kept = Kept::new();
+    for name in manifest.plugins.keys() {
+        if let Some(version) = global_version(home, name) {
+            kept.entry(name.clone()).or_default().insert(version);
+        }
+    }
+
+    let mut pin_files = SeenProjects::load(home)?.pin_files;
+    pin_files.extend(find_pin_file(dir));
+    let mut forgotten = vec![];
+    for pin_file in pin_files {
+        if !pin_file.is_file() {
+            forgotten.push(pin_file);
+            continue;
+        }
+        let pins = match PinFile::load(&pin_file) {
+            Ok(pins) => pins,
+            Err(e) => {
+                warn!("{:?}", e);
+                continue;
+            }
+        };
+        for pin in &pins.pins {
+            if let Some(version) = installed_match(home, &pin.name, &pin.version_req()?)? {
+                kept.entry(pin.name.clone()).or_default().insert(version);
+            }
+        }
+        let lock_file = lock_path(&pin_file);
+        if lock_file.is_file() {
+            for (name, locked) in LockFile::load(&lock_file)?.plugins {
+                kept.entry(name).or_default().insert(locked.version);
+            }
+        }
+    }
+    Ok((kept, forgotten))
+}
+
+// <cache>/<sha256> entries unused for max_age, then the oldest until the rest fits in max_size
+pub fn prune_cache(
+    cache: &Path,
+    max_age: Option<Duration>,
+    max_size: Option<u64>,
+    dry_run: bool,
+) -> Result<Vec<(PathBuf, u64)>> {
+    if !cache.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut entries = vec![];
+    for entry in fs::read_dir(cache)? {
+        let path = entry?.path();
+        // .fetch-* dirs are downloads in progress
+        if !is_cache_entry(&path) {
+            continue;
+        }
+        entries.push((last_used(&path)?, path.clone(), dir_size(&path)));
+    }
+    entries.sort();
+
+    let now = SystemTime::now();
+    let mut total: u64 = entries.iter().map(|(_, _, size)| size).sum();
+    let mut pruned = vec![];
+    for (used, path, size) in entries {
+        let age = now.duration_since(used).unwrap_or_default();
+        let too_old = max_age.is_some_and(|max| age > max);
+        let too_big = max_size.is_some_and(|max| total > max);
+        if !too_old && !too_big {
+            continue;
+        }
+        if !dry_run {
+            fs::remove_dir_all(&path)?;
+        }
+        total -= size;
+        pruned.push((path, size));
+    }
+    Ok(pruned)
+}
+
+// a <sha256> dir, anything else in the cache isn't gc's to remove
+fn is_cache_entry(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    name.len() == 64 && name.chars().all(|c| c.is_ascii_hexdigit()) && path.is_dir()
+}
+
+// newest mtime of the archives in a cache entry, fetch touches them on a cache hit
+fn last_used(entry: &Path) -> Result<SystemTime> {
+    let mut last = SystemTime::UNIX_EPOCH;
+    for file in fs::read_dir(entry)? {
+        last = last.max(file?.metadata()?.modified()?);
+    }
+    Ok(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::active::set_global;
+    use crate::plugin::install::tests::make_archive;
+    use crate::plugin::install::{install, InstallOptions};
+    use crate::plugin::pins::{write_pin, PIN_FILE};
+    use crate::plugin::source::Source;
+
+    #[test]
+    fn test_gc() {
+        let home = tempfile::tempdir().unwrap();
+        let project = tempfile::tempdir().unwrap();
+        let elsewhere = tempfile::tempdir().unwrap();
+        let source_dir = tempfile::tempdir().unwrap();
+        let source = Source::parse(source_dir.path().to_str().unwrap());
+        for version in ["1.0.0", "1.1.0", "2.0.0"] {
+            make_archive(source_dir.path(), "hello", version);
+            let spec = PluginSpec::parse(&format!("hello@{}", version)).unwrap();
+            install(home.path(), &source, &spec, &InstallOptions::default()).unwrap();
+        }
+        set_global(home.path(), "hello", &Version::parse("2.0.0").unwrap()).unwrap();
+        let pin_file = project.path().join(PIN_FILE);
+        write_pin(&pin_file, &PluginSpec::parse("hello@1.0.0").unwrap()).unwrap();
+        record_project(home.path(), &pin_file).unwrap();
+
+        let options = GcOptions {
+            dry_run: true,
+            ..GcOptions::default()
+        };
+        let report = gc(home.path(), elsewhere.path(), &options).unwrap();
+        assert_eq!(report.versions.len(), 1);
+        assert_eq!(report.versions[0].1, Version::parse("1.1.0").unwrap());
+        assert!(report.reclaimed() > 0);
+        assert!(version_dir(home.path(), "hello", "1.1.0").is_dir());
+
+        let report = gc(home.path(), elsewhere.path(), &GcOptions::default()).unwrap();
+        assert_eq!(report.versions.len(), 1);
+        assert!(!version_dir(home.path(), "hello", "1.1.0").exists());
+
+        // once the project is gone its pin no longer keeps 1.0.0
+        fs::remove_file(&pin_file).unwrap();
+        let report = gc(home.path(), elsewhere.path(), &GcOptions::default()).unwrap();
+        assert_eq!(report.forgotten.len(), 1);
+        assert_eq!(report.versions[0].1, Version::parse("1.0.0").unwrap());
+        assert!(SeenProjects::load(home.path())
+            .unwrap()
+            .pin_files
+            .is_empty());
+        let manifest = InstallManifest::load(home.path()).unwrap();
+        assert_eq!(manifest.versions("hello").len(), 1);
+    }
+
+    #[test]
+    fn test_prune_cache() {
+        let cache = tempfile::tempdir().unwrap();
+        let entry = |c: char| cache.path().join(c.to_string().repeat(64));
+        for (sha, content) in [('a', "1234"), ('b', "12")] {
+            fs::create_dir(entry(sha)).unwrap();
+            fs::write(entry(sha).join("a.tar.gz"), content).unwrap();
+        }
+        // a download in progress is never pruned
+        let fetching = cache.path().join(".fetch-x");
+        fs::create_dir(&fetching).unwrap();
+        fs::write(fetching.join("a.tar.gz"), "123456").unwrap();
+        let pruned = prune_cache(cache.path(), Some(Duration::from_secs(3600)), None, false);
+        assert!(pruned.unwrap().is_empty());
+        let pruned = prune_cache(cache.path(), None, Some(4), true).unwrap();
+        assert_eq!(pruned.len(), 1);
+        let pruned = prune_cache(cache.path(), Some(Duration::ZERO), None, false).unwrap();
+        assert_eq!(pruned.iter().map(|(_, size)| size).sum::<u64>(), 6);
+        assert_eq!(fs::read_dir(cache.path()).unwrap().count(), 1);
+        assert!(fetching.join("a.tar.gz").is_file());
+
+        // age is the archive's last use, not when its entry was created
+        for sha in ['c', 'd'] {
+            fs::create_dir(entry(sha)).unwrap();
+            fs::write(entry(sha).join("a.tar.gz"), "1").unwrap();
+        }
+        fs::File::options()
+            .write(true)
+            .open(entry('c').join("a.tar.gz"))
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000))
+            .unwrap();
+        let pruned = prune_cache(cache.path(), Some(Duration::from_secs(3600)), None, false);
+        assert_eq!(pruned.unwrap()[0].0, entry('c'));
+        assert!(entry('d').exists());
+    }
+}
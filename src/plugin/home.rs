@@ -0,0 +1,52 @@
+use anyhow::{Context, Result};
+use dirs::home_dir;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::util::expand_tilde;
+
+// COT_HOME if set, otherwise ~/.cot
+pub fn cot_home() -> Result<PathBuf> {
+    let home = match env::var("COT_HOME") {
+        Ok(value) if !value.is_empty() => PathBuf::from(
+            expand_tilde(&value).with_context(|| format!("Failed to expand COT_HOME {}", value))?,
+        ),
+        _ => home_dir()
+            .with_context(|| "Failed to get HOME directory")?
+            .join(".cot"),
+    };
+    fs::create_dir_all(&home)
+        .with_context(|| format!("Failed to create COT_HOME {}", home.display()))?;
+    Ok(home)
+}
+
+pub fn plugins_dir(home: &Path) -> PathBuf {
+    home.join("plugins")
+}
+
+pub fn plugin_dir(home: &Path, name: &str) -> PathBuf {
+    plugins_dir(home).join(name)
+}
+
+pub fn shims_dir(home: &Path) -> PathBuf {
+    home.join("shims")
+}
+
+pub fn version_dir(home: &Path, name: &str, version: &str) -> PathBuf {
+    plugin_dir(home, name).join(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_dir() {
+        let home = PathBuf::from("/opt/cot");
+        assert_eq!(
+            version_dir(&home, "node", "20.1.0"),
+            PathBuf::from("/opt/cot/plugins/node/20.1.0")
+        );
+    }
+}
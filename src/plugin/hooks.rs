@@ -0,0 +1,172 @@
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use semver::Version;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::process::Command;
+
+use super::descriptor::PluginDescriptor;
+use crate::util::replace_envs;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Hook {
+    PreInstall,
+    PostInstall,
+    PreRemove,
+}
+
+impl fmt::Display for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Hook::PreInstall => write!(f, "pre-install"),
+            Hook::PostInstall => write!(f, "post-install"),
+            Hook::PreRemove => write!(f, "pre-remove"),
+        }
+    }
+}
+
+// what hooks and the plugin's own env can refer to as ${PLUGIN_DIR} and friends
+pub fn plugin_vars(
+    home: &Path,
+    name: &str,
+    version: &Version,
+    dir: &Path,
+) -> HashMap<String, String> {
+    HashMap::from([
+        ("COT_HOME".to_string(), home.to_string_lossy().to_string()),
+        ("PLUGIN_NAME".to_string(), name.to_string()),
+        ("PLUGIN_VERSION".to_string(), version.to_string()),
+        ("PLUGIN_DIR".to_string(), dir.to_string_lossy().to_string()),
+    ])
+}
+
+// the env of the descriptor with ${PLUGIN_DIR} and friends expanded, in name order
+pub fn plugin_env(
+    descriptor: &PluginDescriptor,
+    vars: &HashMap<String, String>,
+) -> Vec<(String, String)> {
+    descriptor
+        .env
+        .iter()
+        .map(|(name, value)| (name.clone(), replace_envs(value, Some(vars))))
+        .collect()
+}
+
+// run a hook of the descriptor in dir, with the vars expanded and in its environment
+pub fn run_hook(
+    descriptor: &PluginDescriptor,
+    hook: Hook,
+    dir: &Path,
+    vars: &HashMap<String, String>,
+) -> Result<()> {
+    let script = match hook {
+        Hook::PreInstall => &descriptor.hooks.pre_install,
+        Hook::PostInstall => &descriptor.hooks.post_install,
+        Hook::PreRemove => &descriptor.hooks.pre_remove,
+    };
+    let Some(script) = script else {
+        return Ok(());
+    };
+    // only ${PLUGIN_DIR} and friends, the shell expands the rest itself
+    let script = replace_envs(script, Some(vars));
+    debug!("{} hook in {}: {}", hook, dir.display(), script);
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg(&script);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(&script);
+        command
+    };
+    let status = command
+        .current_dir(dir)
+        .envs(vars)
+        .envs(plugin_env(descriptor, vars))
+        .status()
+        .with_context(|| format!("Failed to run {} hook {:?}", hook, script))?;
+    if !status.success() {
+        return Err(anyhow!(
+            "{} hook {:?} failed: exit code {}",
+            hook,
+            script,
+            status.code().unwrap_or(-1)
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::descriptor::Hooks;
+    use std::fs;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_hook() {
+        let dir = tempfile::tempdir().unwrap();
+        let descriptor = PluginDescriptor {
+            hooks: Hooks {
+                post_install: Some(
+                    "echo ${PLUGIN_NAME}@${PLUGIN_VERSION} > out; for f in a b; do echo $f; done >> out; echo héllo $PLUGIN_NAME >> out"
+                        .to_string(),
+                ),
+                pre_remove: Some("exit 3".to_string()),
+                ..Hooks::default()
+            },
+            ..PluginDescriptor::default()
+        };
+        let vars = plugin_vars(
+            Path::new("/opt/cot"),
+            "hello",
+            &Version::parse("1.0.0").unwrap(),
+            dir.path(),
+        );
+        run_hook(&descriptor, Hook::PreInstall, dir.path(), &vars).unwrap();
+        run_hook(&descriptor, Hook::PostInstall, dir.path(), &vars).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join("out")).unwrap(),
+            "hello@1.0.0\na\nb\nhéllo hello\n"
+        );
+        let e = run_hook(&descriptor, Hook::PreRemove, dir.path(), &vars).unwrap_err();
+        assert!(e.to_string().contains("exit code 3"));
+    }
+
+    #[test]
+    fn test_plugin_env() {
+        let descriptor = PluginDescriptor {
+            env: [
+                ("JAVA_HOME".to_string(), "${PLUGIN_DIR}/jre".to_string()),
+                ("JAVA_TOOL".to_string(), "${PLUGIN_NAME}".to_string()),
+                (
+                    "JAVA_OPTS".to_string(),
+                    "-Dname=jävа %TEMP% $x.y ${HOME}".to_string(),
+                ),
+            ]
+            .into(),
+            ..PluginDescriptor::default()
+        };
+        let vars = plugin_vars(
+            Path::new("/opt/cot"),
+            "java",
+            &Version::parse("21.0.0").unwrap(),
+            Path::new("/opt/cot/plugins/java/21.0.0"),
+        );
+        assert_eq!(
+            plugin_env(&descriptor, &vars),
+            vec![
+                (
+                    "JAVA_HOME".to_string(),
+                    "/opt/cot/plugins/java/21.0.0/jre".to_string()
+                ),
+                (
+                    "JAVA_OPTS".to_string(),
+                    "-Dname=jävа %TEMP% $x.y ${HOME}".to_string()
+                ),
+                ("JAVA_TOOL".to_string(), "java".to_string()),
+            ]
+        );
+    }
+}
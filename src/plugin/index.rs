@@ -0,0 +1,322 @@
+use anyhow::{anyhow, Context, Result};
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env::consts;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const INDEX_FILE: &str = "index.yaml";
+pub const INDEX_FORMAT: u32 = 1;
+
+/// A plugin index, such as
+///
+/// ```yaml
+/// version: 1
+/// plugins:
+///   node:
+///     description: Node.js runtime
+///     releases:
+///       - version: 20.1.0
+///         artifacts:
+///           - os: linux
+///             arch: x86_64
+///             url: node/node-20.1.0-linux-x64.tar.gz
+///             sha256: 5d9d2a...
+///             signature: |     # optional, the .minisig of the archive
+///               untrusted comment: signature from minisign secret key
+///               RUQf6LRCGA9i...
+///               trusted comment: timestamp:1556193335
+///               y/rUw2y8/hOU...
+///           - url: node/node-20.1.0.zip   # no os/arch, any platform
+///             sha256: 0f3c41...
+/// ```
+///
+/// Relative urls are relative to the directory of the index file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PluginIndex {
+    pub version: u32,
+    #[serde(default)]
+    pub plugins: BTreeMap<String, IndexPlugin>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IndexPlugin {
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub releases: Vec<Release>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Release {
+    pub version: Version,
+    // other plugins this release needs, `name` or `name@requirement`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends: Vec<String>,
+    #[serde(default)]
+    pub artifacts: Vec<IndexArtifact>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexArtifact {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arch: Option<String>,
+    pub url: String,
+    pub sha256: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+/// The release and artifact picked for one plugin on one platform.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resolved {
+    pub name: String,
+    pub version: Version,
+    pub depends: Vec<String>,
+    pub artifact: IndexArtifact,
+    // the release's artifacts for every platform, what a lockfile keeps
+    pub artifacts: Vec<IndexArtifact>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Platform {
+    pub os: String,
+    pub arch: String,
+}
+
+impl Platform {
+    pub fn current() -> Platform {
+        Platform {
+            os: normalize_os(consts::OS),
+            arch: normalize_arch(consts::ARCH),
+        }
+    }
+}
+
+// darwin/osx => macos, win => windows
+fn normalize_os(os: &str) -> String {
+    match os.to_lowercase().as_str() {
+        "darwin" | "osx" | "mac" => "macos".to_string(),
+        "win" | "win32" | "win64" => "windows".to_string(),
+        other => other.to_string(),
+    }
+}
+
+// amd64/x64 => x86_64, arm64 => aarch64
+fn normalize_arch(arch: &str) -> String {
+    match arch.to_lowercase().as_str() {
+        "amd64" | "x64" => "x86_64".to_string(),
+        "arm64" => "aarch64".to_string(),
+        "i386" | "i686" | "x86_32" => "x86".to_string(),
+        other => other.to_string(),
+    }
+}
+
+impl IndexArtifact {
+    // 2 for exact os and arch, 1 when either is unspecified, None when it doesn't fit
+    fn score(&self, platform: &Platform) -> Option<u8> {
+        let os = match &self.os {
+            Some(os) if normalize_os(os) != platform.os => return None,
+            Some(_) => 1,
+            None => 0,
+        };
+        let arch = match &self.arch {
+            Some(arch) if normalize_arch(arch) != platform.arch => return None,
+            Some(_) => 1,
+            None => 0,
+        };
+        Some(os + arch)
+    }
+}
+
+impl PluginIndex {
+    pub fn load(path: &Path) -> Result<PluginIndex> {
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read plugin index {}", path.display()))?;
+        PluginIndex::parse(&data)
+            .with_context(|| format!("Failed to parse plugin index {}", path.display()))
+    }
+
+    pub fn parse(data: &str) -> Result<PluginIndex> {
+        let index: PluginIndex = serde_yaml::from_str(data)?;
+        if index.version != INDEX_FORMAT {
+            return Err(anyhow!(
+                "unsupported index version {}, expected {}",
+                index.version,
+                INDEX_FORMAT
+            ));
+        }
+        Ok(index)
+    }
+
+    // highest version satisfying req that has an artifact for the platform
+    pub fn resolve(&self, name: &str, req: &VersionReq, platform: &Platform) -> Result<Resolved> {
+        let plugin = self
+            .plugins
+            .get(name)
+            .ok_or_else(|| anyhow!("plugin {} is not in the index", name))?;
+        let mut releases: Vec<&Release> = plugin
+            .releases
+            .iter()
+            .filter(|r| req.matches(&r.version))
+            .collect();
+        releases.sort_by(|a, b| b.version.cmp(&a.version));
+        for release in releases {
+            if let Some(artifact) = best_artifact(&release.artifacts, platform) {
+                return Ok(Resolved {
+                    name: name.to_string(),
+                    version: release.version.clone(),
+                    depends: release.depends.clone(),
+                    artifact: artifact.clone(),
+                    artifacts: release.artifacts.clone(),
+                });
+            }
+        }
+        Err(anyhow!(
+            "no release of {} matching {} for {}-{}",
+            name,
+            req,
+            platform.os,
+            platform.arch
+        ))
+    }
+
+    // released versions of name that have an artifact for the platform, ascending
+    pub fn versions(&self, name: &str, platform: &Platform) -> Vec<Version> {
+        let mut versions: Vec<Version> = self
+            .plugins
+            .get(name)
+            .map(|p| p.releases.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .filter(|r| r.artifacts.iter().any(|a| a.score(platform).is_some()))
+            .map(|r| r.version.clone())
+            .collect();
+        versions.sort();
+        versions
+    }
+}
+
+// the artifact fitting platform best, one for its exact os and arch before any-platform ones
+pub fn best_artifact<'a>(
+    artifacts: &'a [IndexArtifact],
+    platform: &Platform,
+) -> Option<&'a IndexArtifact> {
+    artifacts
+        .iter()
+        .filter_map(|a| a.score(platform).map(|score| (score, a)))
+        .max_by_key(|(score, _)| *score)
+        .map(|(_, artifact)| artifact)
+}
+
+pub fn is_remote(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+// remote urls as they are, file:// and relative ones as a path, relative to the index directory
+pub fn artifact_url(index_dir: &Path, url: &str) -> String {
+    if is_remote(url) {
+        return url.to_string();
+    }
+    let path = PathBuf::from(url.strip_prefix("file://").unwrap_or(url));
+    if path.is_absolute() {
+        path.to_string_lossy().to_string()
+    } else {
+        index_dir.join(path).to_string_lossy().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INDEX: &str = r#"
+version: 1
+plugins:
+  node:
+    description: Node.js runtime
+    releases:
+      - version: 18.0.0
+        artifacts:
+          - url: node-18.0.0.tar.gz
+            sha256: "18"
+      - version: 20.1.0
+        artifacts:
+          - os: linux
+            arch: amd64
+            url: node-20.1.0-linux-x64.tar.gz
+            sha256: "20-linux"
+          - os: darwin
+            url: node-20.1.0-darwin.tar.gz
+            sha256: "20-macos"
+      - version: 21.0.0-rc.1
+        artifacts:
+          - url: node-21.0.0-rc.1.tar.gz
+            sha256: "21"
+"#;
+
+    fn platform(os: &str, arch: &str) -> Platform {
+        Platform {
+            os: os.to_string(),
+            arch: arch.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_resolve() {
+        let index = PluginIndex::parse(INDEX).unwrap();
+        let linux = platform("linux", "x86_64");
+        let resolved = index.resolve("node", &VersionReq::STAR, &linux).unwrap();
+        assert_eq!(resolved.version, Version::parse("20.1.0").unwrap());
+        assert_eq!(resolved.artifact.sha256, "20-linux");
+
+        let resolved = index
+            .resolve("node", &VersionReq::STAR, &platform("macos", "aarch64"))
+            .unwrap();
+        assert_eq!(resolved.artifact.sha256, "20-macos");
+
+        // no 20.x for windows, falls back to the any-platform 18.0.0
+        let resolved = index
+            .resolve("node", &VersionReq::STAR, &platform("windows", "x86_64"))
+            .unwrap();
+        assert_eq!(resolved.version, Version::parse("18.0.0").unwrap());
+
+        let req = VersionReq::parse(">=21.0.0-rc.0").unwrap();
+        let resolved = index.resolve("node", &req, &linux).unwrap();
+        assert_eq!(resolved.artifact.sha256, "21");
+
+        assert!(index
+            .resolve("node", &VersionReq::parse("^19").unwrap(), &linux)
+            .is_err());
+        assert!(index.resolve("deno", &VersionReq::STAR, &linux).is_err());
+
+        let versions: Vec<String> = index
+            .versions("node", &platform("windows", "x86_64"))
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        assert_eq!(versions, vec!["18.0.0", "21.0.0-rc.1"]);
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        assert!(PluginIndex::parse("version: 2\nplugins: {}\n").is_err());
+    }
+
+    #[test]
+    fn test_artifact_url() {
+        let dir = Path::new("/srv/index");
+        assert_eq!(
+            artifact_url(dir, "node/a.tar.gz"),
+            "/srv/index/node/a.tar.gz"
+        );
+        assert_eq!(artifact_url(dir, "file:///tmp/a.zip"), "/tmp/a.zip");
+        assert_eq!(
+            artifact_url(dir, "https://example.com/a.zip"),
+            "https://example.com/a.zip"
+        );
+    }
+}
@@ -0,0 +1,417 @@
+use anyhow::{anyhow, Context, Result};
+use log::{debug, error, trace};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::cache::{fetch, FetchOptions};
+use super::descriptor::PluginDescriptor;
+use super::home::{plugin_dir, version_dir};
+use super::hooks::{plugin_vars, run_hook, Hook};
+use super::manifest::{InstallManifest, InstalledVersion};
+use super::pins::{find_pin_file, PinFile, PIN_FILE};
+use super::projects::record_project;
+use super::source::{archive_extension, Artifact, Source};
+use super::spec::PluginSpec;
+use super::verify::{public_key, verify_artifact};
+use crate::util;
+
+#[derive(Debug)]
+pub enum InstallOutcome {
+    Installed(Artifact, PathBuf),
+    AlreadyInstalled(Artifact, PathBuf),
+}
+
+impl fmt::Display for InstallOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstallOutcome::Installed(artifact, path) => write!(
+                f,
+                "{}@{} installed to {}",
+                artifact.name,
+                artifact.version,
+                path.display()
+            ),
+            InstallOutcome::AlreadyInstalled(artifact, path) => write!(
+                f,
+                "{}@{} already installed in {}",
+                artifact.name,
+                artifact.version,
+                path.display()
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct InstallOptions {
+    // reinstall versions that are already installed
+    pub force: bool,
+    pub fetch: FetchOptions,
+}
+
+pub fn install(
+    home: &Path,
+    source: &Source,
+    spec: &PluginSpec,
+    options: &InstallOptions,
+) -> Result<InstallOutcome> {
+    let artifact = source.locate(&spec.name, &spec.version_req()?)?;
+    install_artifact(home, artifact, options)
+}
+
+// install an artifact picked from a source or a lockfile
+pub fn install_artifact(
+    home: &Path,
+    artifact: Artifact,
+    options: &InstallOptions,
+) -> Result<InstallOutcome> {
+    let name = artifact.name.clone();
+    let version = artifact.version.to_string();
+    let target = version_dir(home, &name, &version);
+    let mut manifest = InstallManifest::load(home)?;
+    if !options.force && target.is_dir() && manifest.get(&name, &artifact.version).is_some() {
+        return Ok(InstallOutcome::AlreadyInstalled(artifact, target));
+    }
+
+    // nothing is unpacked before the checksum and signature are checked
+    let archive = fetch(&artifact, &options.fetch)?;
+    verify_archive(&archive)?;
+    let sha256 = verify_artifact(&artifact, &archive, public_key(home)?.as_ref())?;
+
+    // unpack next to the final location, so moving it into place is a rename
+    let parent = plugin_dir(home, &name);
+    fs::create_dir_all(&parent)?;
+    let staging = tempfile::Builder::new()
+        .prefix(".staging-")
+        .tempdir_in(&parent)?;
+    let unpacked = staging.path().join("unpacked");
+    fs::create_dir(&unpacked)?;
+    unpack(&archive, &unpacked)
+        .with_context(|| format!("Failed to unpack {}", archive.display()))?;
+    let root = content_root(&unpacked)?;
+    trace!("install {}@{} from {}", name, version, root.display());
+
+    let descriptor = PluginDescriptor::load(&root)?;
+    let vars = plugin_vars(home, &name, &artifact.version, &root);
+    run_hook(&descriptor, Hook::PreInstall, &root, &vars)?;
+
+    // a reinstalled version stays aside until the new one made it through post-install
+    let previous = staging.path().join("previous");
+    if target.exists() {
+        fs::rename(&target, &previous)
+            .with_context(|| format!("Failed to move {} aside", target.display()))?;
+    }
+    let moved = fs::rename(&root, &target)
+        .with_context(|| format!("Failed to move {} into place", target.display()));
+    if let Err(e) = moved {
+        // the working install must not go with the staging dir
+        if previous.exists() {
+            fs::rename(&previous, &target)
+                .with_context(|| format!("Failed to restore {}", target.display()))?;
+        }
+        return Err(e);
+    }
+    let vars = plugin_vars(home, &name, &artifact.version, &target);
+    if let Err(e) = run_hook(&descriptor, Hook::PostInstall, &target, &vars) {
+        // the failed version goes with the staging dir, the previous one must not
+        let failed = staging.path().join("failed");
+        if let Err(remove) = fs::rename(&target, &failed).or_else(|_| fs::remove_dir_all(&target)) {
+            error!("Failed to remove {}: {:?}", target.display(), remove);
+        }
+        if previous.exists() {
+            fs::rename(&previous, &target)
+                .with_context(|| format!("Failed to restore {}", target.display()))?;
+        }
+        return Err(e.context(format!("Failed to install {}@{}", name, version)));
+    }
+
+    manifest.add(
+        &name,
+        InstalledVersion {
+            version: artifact.version.clone(),
+            source: artifact.url.clone(),
+            sha256: Some(sha256),
+            depends: artifact.depends.clone(),
+            installed_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        },
+    );
+    manifest.save(home)?;
+    debug!("installed {}@{} to {}", name, version, target.display());
+    Ok(InstallOutcome::Installed(artifact, target))
+}
+
+// everything pinned in the .cot-versions that applies to dir
+pub fn install_pinned(
+    home: &Path,
+    source: &Source,
+    dir: &Path,
+    options: &InstallOptions,
+) -> Result<(PathBuf, Vec<InstallOutcome>)> {
+    let path = find_pin_file(dir)
+        .ok_or_else(|| anyhow!("no {} found from {}", PIN_FILE, dir.display()))?;
+    let pins = PinFile::load(&path)?;
+    record_project(home, &path)?;
+    let mut outcomes = vec![];
+    for spec in &pins.pins {
+        outcomes.push(install(home, source, spec, options)?);
+    }
+    Ok((path, outcomes))
+}
+
+fn verify_archive(path: &Path) -> Result<()> {
+    let metadata =
+        fs::metadata(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    if !metadata.is_file() || metadata.len() == 0 {
+        return Err(anyhow!("{} is not a plugin archive", path.display()));
+    }
+    Ok(())
+}
+
+// the util file helpers take &str, an empty one would work on the current directory
+pub fn utf8_path(path: &Path) -> Result<&str> {
+    path.to_str()
+        .ok_or_else(|| anyhow!("{} is not a valid UTF-8 path", path.display()))
+}
+
+pub fn unpack(archive: &Path, dest: &Path) -> Result<()> {
+    let file_name = archive.file_name().unwrap_or_default().to_string_lossy();
+    let archive_str = utf8_path(archive)?;
+    let dest_str = utf8_path(dest)?;
+    match archive_extension(&file_name) {
+        Some(".tar.gz") | Some(".tgz") => {
+            let temp_dir = tempfile::tempdir()?;
+            let tar_file = temp_dir.path().join("archive.tar");
+            let tar_str = utf8_path(&tar_file)?;
+            util::decompress(archive_str, tar_str)?;
+            util::extract(tar_str, dest_str)
+        }
+        Some(".tar") => util::extract(archive_str, dest_str),
+        Some(".zip") => util::unzip(archive_str, dest_str),
+        _ => Err(anyhow!("unknown archive type {:?}", file_name)),
+    }
+}
+
+// archives usually wrap everything in a single `<name>-<version>/` directory, drop it
+pub fn content_root(unpacked: &Path) -> Result<PathBuf> {
+    let entries: Vec<PathBuf> = fs::read_dir(unpacked)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    if entries.len() == 1 && entries[0].is_dir() {
+        return Ok(entries[0].clone());
+    }
+    Ok(unpacked.to_path_buf())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use semver::Version;
+
+    // <dir>/<name>-<version>.tar.gz wrapping <name>-<version>/bin/<name>
+    pub(crate) fn make_archive(dir: &Path, name: &str, version: &str) -> PathBuf {
+        make_archive_with(dir, name, version, None)
+    }
+
+    // same, with descriptor as its cot-plugin.yaml
+    pub(crate) fn make_archive_with(
+        dir: &Path,
+        name: &str,
+        version: &str,
+        descriptor: Option<&str>,
+    ) -> PathBuf {
+        let path = dir.join(format!("{}-{}.tar.gz", name, version));
+        let file = fs::File::create(&path).unwrap();
+        let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+        let script = format!("#!/bin/sh\necho {} {}\n", name, version);
+        let mut files = vec![(format!("bin/{}", name), script, 0o755)];
+        if let Some(descriptor) = descriptor {
+            files.push((
+                crate::plugin::descriptor::DESCRIPTOR_FILE.to_string(),
+                descriptor.to_string(),
+                0o644,
+            ));
+        }
+        for (file_name, content, mode) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(mode);
+            header.set_cksum();
+            builder
+                .append_data(
+                    &mut header,
+                    format!("{}-{}/{}", name, version, file_name),
+                    content.as_bytes(),
+                )
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn test_install() {
+        let home = tempfile::tempdir().unwrap();
+        let source_dir = tempfile::tempdir().unwrap();
+        make_archive(source_dir.path(), "hello", "1.0.0");
+        make_archive(source_dir.path(), "hello", "1.1.0");
+        let source = Source::parse(source_dir.path().to_str().unwrap());
+
+        let spec = PluginSpec::parse("hello@1.0.0").unwrap();
+        match install(home.path(), &source, &spec, &InstallOptions::default()).unwrap() {
+            InstallOutcome::Installed(artifact, path) => {
+                assert_eq!(artifact.version, Version::parse("1.0.0").unwrap());
+                assert!(path.join("bin").join("hello").is_file());
+            }
+            outcome => panic!("unexpected {:?}", outcome),
+        }
+        assert!(matches!(
+            install(home.path(), &source, &spec, &InstallOptions::default()).unwrap(),
+            InstallOutcome::AlreadyInstalled(_, _)
+        ));
+
+        let spec = PluginSpec::parse("hello").unwrap();
+        install(home.path(), &source, &spec, &InstallOptions::default()).unwrap();
+        let manifest = InstallManifest::load(home.path()).unwrap();
+        assert_eq!(manifest.versions("hello").len(), 2);
+        // no staging directories left behind
+        let leftovers = fs::read_dir(plugin_dir(home.path(), "hello"))
+            .unwrap()
+            .filter(|e| {
+                e.as_ref()
+                    .unwrap()
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with('.')
+            })
+            .count();
+        assert_eq!(leftovers, 0);
+    }
+
+    #[test]
+    fn test_install_pinned() {
+        let home = tempfile::tempdir().unwrap();
+        let source_dir = tempfile::tempdir().unwrap();
+        make_archive(source_dir.path(), "hello", "1.0.0");
+        make_archive(source_dir.path(), "hello", "2.0.0");
+        make_archive(source_dir.path(), "world", "0.1.0");
+        let source = Source::parse(source_dir.path().to_str().unwrap());
+        let project = tempfile::tempdir().unwrap();
+        assert!(install_pinned(
+            home.path(),
+            &source,
+            project.path(),
+            &InstallOptions::default()
+        )
+        .is_err());
+
+        fs::write(project.path().join(PIN_FILE), "hello@^1\nworld@0.1.0\n").unwrap();
+        let (path, outcomes) = install_pinned(
+            home.path(),
+            &source,
+            project.path(),
+            &InstallOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(path, project.path().join(PIN_FILE));
+        assert_eq!(outcomes.len(), 2);
+        let manifest = InstallManifest::load(home.path()).unwrap();
+        assert_eq!(
+            manifest.versions("hello")[0].version,
+            Version::parse("1.0.0").unwrap()
+        );
+        assert_eq!(manifest.versions("world").len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_install_hooks() {
+        let home = tempfile::tempdir().unwrap();
+        let source_dir = tempfile::tempdir().unwrap();
+        let source = Source::parse(source_dir.path().to_str().unwrap());
+        make_archive_with(
+            source_dir.path(),
+            "hooked",
+            "1.0.0",
+            Some("hooks:\n  pre-install: touch pre\n  post-install: echo $PLUGIN_VERSION > ${PLUGIN_DIR}/post\n"),
+        );
+        make_archive_with(
+            source_dir.path(),
+            "hooked",
+            "2.0.0",
+            Some("hooks:\n  post-install: exit 1\n"),
+        );
+        let spec = PluginSpec::parse("hooked@1.0.0").unwrap();
+        install(home.path(), &source, &spec, &InstallOptions::default()).unwrap();
+        let dir = version_dir(home.path(), "hooked", "1.0.0");
+        assert!(dir.join("pre").is_file());
+        assert_eq!(fs::read_to_string(dir.join("post")).unwrap(), "1.0.0\n");
+
+        // failing post-install rolls back
+        let spec = PluginSpec::parse("hooked@2.0.0").unwrap();
+        assert!(install(home.path(), &source, &spec, &InstallOptions::default()).is_err());
+        assert!(!version_dir(home.path(), "hooked", "2.0.0").exists());
+        let manifest = InstallManifest::load(home.path()).unwrap();
+        assert_eq!(manifest.versions("hooked").len(), 1);
+
+        // a failing reinstall puts the previous install back
+        make_archive_with(
+            source_dir.path(),
+            "hooked",
+            "1.0.0",
+            Some("hooks:\n  post-install: exit 1\n"),
+        );
+        let spec = PluginSpec::parse("hooked@1.0.0").unwrap();
+        let options = InstallOptions {
+            force: true,
+            ..InstallOptions::default()
+        };
+        assert!(install(home.path(), &source, &spec, &options).is_err());
+        assert_eq!(fs::read_to_string(dir.join("post")).unwrap(), "1.0.0\n");
+
+        // so does one that can't be moved into place
+        make_archive_with(
+            source_dir.path(),
+            "hooked",
+            "1.0.0",
+            Some("hooks:\n  pre-install: rm -rf \"${PLUGIN_DIR}\"\n"),
+        );
+        assert!(install(home.path(), &source, &spec, &options).is_err());
+        assert_eq!(fs::read_to_string(dir.join("post")).unwrap(), "1.0.0\n");
+    }
+
+    #[test]
+    fn test_install_bad_archive() {
+        let home = tempfile::tempdir().unwrap();
+        let source_dir = tempfile::tempdir().unwrap();
+        fs::write(source_dir.path().join("broken-1.0.0.tar.gz"), b"not gzip").unwrap();
+        let source = Source::parse(source_dir.path().to_str().unwrap());
+        let spec = PluginSpec::parse("broken").unwrap();
+        assert!(install(home.path(), &source, &spec, &InstallOptions::default()).is_err());
+        assert!(!version_dir(home.path(), "broken", "1.0.0").exists());
+        assert!(InstallManifest::load(home.path())
+            .unwrap()
+            .plugins
+            .is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unpack_non_utf8_path() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("plugin-1.0.0.zip");
+        fs::write(&archive, b"PK").unwrap();
+        let dest = dir.path().join(OsStr::from_bytes(b"bad\xff"));
+        let err = unpack(&archive, &dest).unwrap_err();
+        assert!(err.to_string().contains("not a valid UTF-8 path"));
+    }
+}
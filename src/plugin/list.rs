@@ -0,0 +1,125 @@
+use anyhow::Result;
+use semver::Version;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use super::active::{active_version, global_version, Origin};
+use super::home::version_dir;
+use super::manifest::InstallManifest;
+use super::source::Source;
+
+#[derive(Debug, Serialize)]
+pub struct InstalledEntry {
+    pub name: String,
+    pub version: Version,
+    pub active: bool,
+    // .cot-versions that selected the active version, none when it is the global one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pinned_in: Option<PathBuf>,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AvailableEntry {
+    pub name: String,
+    pub version: Version,
+    pub installed: bool,
+}
+
+// installed versions, marking the one active in dir
+pub fn list_installed(home: &Path, dir: &Path) -> Result<Vec<InstalledEntry>> {
+    let manifest = InstallManifest::load(home)?;
+    let mut entries = vec![];
+    for (name, versions) in &manifest.plugins {
+        // a pin to a missing version shouldn't hide the list, fall back to global
+        let active = match active_version(home, name, dir) {
+            Ok(active) => active.map(|a| (a.version, a.origin)),
+            Err(_) => global_version(home, name).map(|v| (v, Origin::Global)),
+        };
+        for installed in versions {
+            let (is_active, pinned_in) = match &active {
+                Some((v, Origin::Project(path))) if v == &installed.version => {
+                    (true, Some(path.clone()))
+                }
+                Some((v, Origin::Global)) if v == &installed.version => (true, None),
+                _ => (false, None),
+            };
+            entries.push(InstalledEntry {
+                name: name.clone(),
+                version: installed.version.clone(),
+                active: is_active,
+                pinned_in,
+                path: version_dir(home, name, &installed.version.to_string()),
+            });
+        }
+    }
+    Ok(entries)
+}
+
+pub fn list_available(home: &Path, source: &Source) -> Result<Vec<AvailableEntry>> {
+    let manifest = InstallManifest::load(home)?;
+    let mut entries = vec![];
+    for (name, versions) in source.available()? {
+        for version in versions.into_iter().rev() {
+            entries.push(AvailableEntry {
+                installed: manifest.get(&name, &version).is_some(),
+                name: name.clone(),
+                version,
+            });
+        }
+    }
+    Ok(entries)
+}
+
+// plugin names and name@version of everything installed, for shell completion
+pub fn installed_specs(home: &Path) -> Result<Vec<String>> {
+    let manifest = InstallManifest::load(home)?;
+    let mut specs = vec![];
+    for (name, versions) in &manifest.plugins {
+        specs.push(name.clone());
+        for installed in versions {
+            specs.push(format!("{}@{}", name, installed.version));
+        }
+    }
+    Ok(specs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::active::set_global;
+    use crate::plugin::install::tests::make_archive;
+    use crate::plugin::install::{install, InstallOptions};
+    use crate::plugin::spec::PluginSpec;
+
+    #[test]
+    fn test_list() {
+        let home = tempfile::tempdir().unwrap();
+        let source_dir = tempfile::tempdir().unwrap();
+        make_archive(source_dir.path(), "hello", "1.0.0");
+        make_archive(source_dir.path(), "hello", "2.0.0");
+        let source = Source::parse(source_dir.path().to_str().unwrap());
+        let spec = PluginSpec::parse("hello@1.0.0").unwrap();
+        install(home.path(), &source, &spec, &InstallOptions::default()).unwrap();
+        set_global(home.path(), "hello", &Version::parse("1.0.0").unwrap()).unwrap();
+
+        let installed = list_installed(home.path(), home.path()).unwrap();
+        assert_eq!(installed.len(), 1);
+        assert!(installed[0].active);
+        assert_eq!(installed[0].pinned_in, None);
+        assert_eq!(
+            installed_specs(home.path()).unwrap(),
+            vec!["hello".to_string(), "hello@1.0.0".to_string()]
+        );
+
+        let available = list_available(home.path(), &source).unwrap();
+        let available: Vec<(String, bool)> = available
+            .iter()
+            .map(|a| (a.version.to_string(), a.installed))
+            .collect();
+        assert_eq!(
+            available,
+            vec![("2.0.0".to_string(), false), ("1.0.0".to_string(), true)]
+        );
+    }
+}
@@ -0,0 +1,394 @@
+use anyhow::{anyhow, Context, Result};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::cache::{fetch, FetchOptions};
+use super::index::{artifact_url, best_artifact, IndexArtifact, Platform, PluginIndex};
+use super::install::{install_artifact, InstallOptions, InstallOutcome};
+use super::pins::{find_pin_file, PinFile, PIN_FILE};
+use super::projects::record_project;
+use super::source::{Artifact, Source};
+use super::spec::PluginSpec;
+use super::verify::{public_key, verify_artifact};
+
+pub const LOCK_FILE: &str = ".cot-versions.lock";
+// 2 locks the archives of every platform, 1 only had the one of the platform it was written on
+pub const LOCK_FORMAT: u32 = 2;
+
+/// `.cot-versions.lock` next to `.cot-versions`, the exact archives each pin resolved to, such as
+///
+/// ```yaml
+/// version: 2
+/// plugins:
+///   node:
+///     pin: node@20
+///     version: 20.1.0
+///     artifacts:
+///       - os: linux
+///         arch: x86_64
+///         url: https://example.com/node/node-20.1.0-linux-x64.tar.gz
+///         sha256: 5d9d2a...
+///       - os: macos
+///         arch: aarch64
+///         url: node/node-20.1.0-macos-arm64.tar.gz
+///         sha256: 0f3c41...
+/// ```
+///
+/// The archives are those of every platform the source offers for the version, so a lock
+/// written on one machine installs on the others. Relative urls are relative to the source,
+/// the directory of its index or the directory itself.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LockFile {
+    pub version: u32,
+    #[serde(default)]
+    pub plugins: BTreeMap<String, LockedPlugin>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockedPlugin {
+    // the .cot-versions line it was resolved from
+    pub pin: String,
+    pub version: Version,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends: Vec<String>,
+    pub artifacts: Vec<IndexArtifact>,
+}
+
+impl LockedPlugin {
+    // the locked archive for platform, relative urls resolved against the source at base
+    pub fn artifact(&self, name: &str, platform: &Platform, base: &Path) -> Result<Artifact> {
+        let locked = best_artifact(&self.artifacts, platform).ok_or_else(|| {
+            anyhow!(
+                "{}@{} is locked without an archive for {}-{}",
+                name,
+                self.version,
+                platform.os,
+                platform.arch
+            )
+        })?;
+        Ok(Artifact {
+            name: name.to_string(),
+            version: self.version.clone(),
+            url: artifact_url(base, &locked.url),
+            sha256: Some(locked.sha256.clone()),
+            signature: locked.signature.clone(),
+            depends: self.depends.clone(),
+        })
+    }
+}
+
+pub fn lock_path(pin_file: &Path) -> PathBuf {
+    pin_file.with_file_name(LOCK_FILE)
+}
+
+impl LockFile {
+    pub fn load(path: &Path) -> Result<LockFile> {
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let lock: LockFile = serde_yaml::from_str(&data)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        if lock.version != LOCK_FORMAT {
+            return Err(anyhow!(
+                "{} has lockfile version {}, only {} is supported, run `cot lock` again",
+                path.display(),
+                lock.version,
+                LOCK_FORMAT
+            ));
+        }
+        Ok(lock)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+        temp.write_all(serde_yaml::to_string(self)?.as_bytes())?;
+        temp.persist(path)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    // the locked entry of a pin, none when the pin changed since it was locked
+    pub fn get(&self, pin: &PluginSpec) -> Option<&LockedPlugin> {
+        let locked = self.plugins.get(&pin.name)?;
+        (locked.pin == pin.to_string()).then_some(locked)
+    }
+}
+
+// `cot lock`: resolve every pin around dir against the source and write the lockfile
+pub fn lock(
+    home: &Path,
+    source: &Source,
+    dir: &Path,
+    options: &FetchOptions,
+) -> Result<(PathBuf, LockFile)> {
+    let pin_path = find_pin_file(dir)
+        .ok_or_else(|| anyhow!("no {} found from {}", PIN_FILE, dir.display()))?;
+    let pins = PinFile::load(&pin_path)?;
+    record_project(home, &pin_path)?;
+    let key = public_key(home)?;
+    let mut lock = LockFile {
+        version: LOCK_FORMAT,
+        ..LockFile::default()
+    };
+    for pin in &pins.pins {
+        let req = pin.version_req()?;
+        let locked = match source.index_path() {
+            Some(index_path) => {
+                let index = PluginIndex::load(&index_path)?;
+                let resolved = index.resolve(&pin.name, &req, &Platform::current())?;
+                LockedPlugin {
+                    pin: pin.to_string(),
+                    version: resolved.version,
+                    depends: resolved.depends,
+                    artifacts: resolved.artifacts,
+                }
+            }
+            // archives of a directory fit any platform and have no checksums, take the archive's
+            None => {
+                let artifact = source.locate(&pin.name, &req)?;
+                let archive = fetch(&artifact, options)?;
+                let sha256 = verify_artifact(&artifact, &archive, key.as_ref())?;
+                let url = match Path::new(&artifact.url).strip_prefix(&source.root) {
+                    Ok(relative) => relative.to_string_lossy().replace('\\', "/"),
+                    Err(_) => artifact.url.clone(),
+                };
+                LockedPlugin {
+                    pin: pin.to_string(),
+                    version: artifact.version,
+                    depends: artifact.depends,
+                    artifacts: vec![IndexArtifact {
+                        os: None,
+                        arch: None,
+                        url,
+                        sha256,
+                        signature: artifact.signature,
+                    }],
+                }
+            }
+        };
+        lock.plugins.insert(pin.name.clone(), locked);
+    }
+    let path = lock_path(&pin_path);
+    lock.save(&path)?;
+    Ok((path, lock))
+}
+
+// `cot install --locked`: the pins around dir, or only spec, exactly as locked, never resolving
+pub fn install_locked(
+    home: &Path,
+    source: &Source,
+    dir: &Path,
+    spec: Option<&PluginSpec>,
+    options: &InstallOptions,
+) -> Result<(PathBuf, Vec<InstallOutcome>)> {
+    let pin_path = find_pin_file(dir)
+        .ok_or_else(|| anyhow!("no {} found from {}", PIN_FILE, dir.display()))?;
+    let path = lock_path(&pin_path);
+    if !path.is_file() {
+        return Err(anyhow!("no {}, run `cot lock` first", path.display()));
+    }
+    let lock = LockFile::load(&path)?;
+    let pins = PinFile::load(&pin_path)?;
+    record_project(home, &pin_path)?;
+    let platform = Platform::current();
+    let base = source.base_dir();
+    let mut artifacts = vec![];
+    for pin in &pins.pins {
+        let locked = lock.get(pin).ok_or_else(|| {
+            anyhow!(
+                "{} in {} is not in {}, run `cot lock`",
+                pin,
+                pin_path.display(),
+                path.display()
+            )
+        })?;
+        artifacts.push(locked.artifact(&pin.name, &platform, &base)?);
+    }
+    if let Some(spec) = spec {
+        let req = spec.version_req()?;
+        artifacts.retain(|a| a.name == spec.name && req.matches(&a.version));
+        if artifacts.is_empty() {
+            return Err(anyhow!("{} is not locked in {}", spec, path.display()));
+        }
+    }
+    let mut outcomes = vec![];
+    for artifact in artifacts {
+        outcomes.push(install_artifact(home, artifact, options)?);
+    }
+    Ok((path, outcomes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::install::tests::make_archive;
+    use crate::plugin::pins::write_pin;
+    use crate::util::sha256_file;
+
+    #[test]
+    fn test_lock() {
+        let home = tempfile::tempdir().unwrap();
+        let project = tempfile::tempdir().unwrap();
+        let source_dir = tempfile::tempdir().unwrap();
+        make_archive(source_dir.path(), "hello", "1.0.0");
+        let archive = make_archive(source_dir.path(), "hello", "1.1.0");
+        make_archive(source_dir.path(), "hello", "2.0.0");
+        let source = Source::parse(source_dir.path().to_str().unwrap());
+        let pin_path = project.path().join(PIN_FILE);
+        write_pin(&pin_path, &PluginSpec::parse("hello@1").unwrap()).unwrap();
+
+        assert!(install_locked(
+            home.path(),
+            &source,
+            project.path(),
+            None,
+            &InstallOptions::default()
+        )
+        .is_err());
+        let (path, lock) = lock(
+            home.path(),
+            &source,
+            project.path(),
+            &FetchOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(path, project.path().join(LOCK_FILE));
+        let locked = &lock.plugins["hello"];
+        assert_eq!(locked.version, Version::parse("1.1.0").unwrap());
+        // a source directory is recorded relative to it, so the lock works on other machines
+        assert_eq!(locked.artifacts[0].url, "hello-1.1.0.tar.gz");
+        assert_eq!(
+            locked.artifacts[0].sha256,
+            sha256_file(archive.to_str().unwrap()).unwrap()
+        );
+        assert_eq!(LockFile::load(&path).unwrap(), lock);
+
+        // a newer matching release doesn't change what --locked installs
+        make_archive(source_dir.path(), "hello", "1.2.0");
+        let (_, outcomes) = install_locked(
+            home.path(),
+            &source,
+            project.path(),
+            None,
+            &InstallOptions::default(),
+        )
+        .unwrap();
+        assert!(matches!(&outcomes[0], InstallOutcome::Installed(a, _)
+            if a.version == Version::parse("1.1.0").unwrap()));
+
+        let other = PluginSpec::parse("hello@2").unwrap();
+        assert!(install_locked(
+            home.path(),
+            &source,
+            project.path(),
+            Some(&other),
+            &InstallOptions::default()
+        )
+        .is_err());
+
+        // changing the pin makes the lock stale
+        write_pin(&pin_path, &other).unwrap();
+        let e = install_locked(
+            home.path(),
+            &source,
+            project.path(),
+            None,
+            &InstallOptions::default(),
+        )
+        .unwrap_err();
+        assert!(e.to_string().contains("run `cot lock`"));
+    }
+
+    #[test]
+    fn test_locked_checksum() {
+        let home = tempfile::tempdir().unwrap();
+        let project = tempfile::tempdir().unwrap();
+        let source_dir = tempfile::tempdir().unwrap();
+        make_archive(source_dir.path(), "hello", "1.0.0");
+        let source = Source::parse(source_dir.path().to_str().unwrap());
+        write_pin(
+            &project.path().join(PIN_FILE),
+            &PluginSpec::parse("hello@1.0.0").unwrap(),
+        )
+        .unwrap();
+        let (path, mut lock) = lock(
+            home.path(),
+            &source,
+            project.path(),
+            &FetchOptions::default(),
+        )
+        .unwrap();
+        lock.plugins.get_mut("hello").unwrap().artifacts[0].sha256 = "00".to_string();
+        lock.save(&path).unwrap();
+        let e = install_locked(
+            home.path(),
+            &source,
+            project.path(),
+            None,
+            &InstallOptions::default(),
+        )
+        .unwrap_err();
+        assert!(e.to_string().starts_with("checksum mismatch"));
+    }
+
+    #[test]
+    fn test_lock_every_platform() {
+        let home = tempfile::tempdir().unwrap();
+        let project = tempfile::tempdir().unwrap();
+        let source_dir = tempfile::tempdir().unwrap();
+        fs::write(
+            source_dir.path().join("index.yaml"),
+            "version: 1\nplugins:\n  hello:\n    releases:\n      - version: 1.0.0\n        artifacts:\n          - os: linux\n            url: hello-1.0.0-linux.tar.gz\n            sha256: linux\n          - os: macos\n            arch: aarch64\n            url: https://example.com/hello-1.0.0-macos.tar.gz\n            sha256: macos\n          - url: hello-1.0.0.zip\n            sha256: any\n",
+        )
+        .unwrap();
+        let source = Source::parse(source_dir.path().to_str().unwrap());
+        write_pin(
+            &project.path().join(PIN_FILE),
+            &PluginSpec::parse("hello@1").unwrap(),
+        )
+        .unwrap();
+        let (_, lock) = lock(
+            home.path(),
+            &source,
+            project.path(),
+            &FetchOptions::default(),
+        )
+        .unwrap();
+        let locked = &lock.plugins["hello"];
+        assert_eq!(locked.artifacts.len(), 3);
+
+        let platform = |os: &str, arch: &str| Platform {
+            os: os.to_string(),
+            arch: arch.to_string(),
+        };
+        let base = Path::new("/srv/plugins");
+        let linux = locked
+            .artifact("hello", &platform("linux", "x86_64"), base)
+            .unwrap();
+        assert_eq!(linux.sha256.as_deref(), Some("linux"));
+        assert_eq!(
+            PathBuf::from(linux.url),
+            base.join("hello-1.0.0-linux.tar.gz")
+        );
+        let macos = locked
+            .artifact("hello", &platform("macos", "aarch64"), base)
+            .unwrap();
+        assert_eq!(macos.url, "https://example.com/hello-1.0.0-macos.tar.gz");
+        let windows = locked
+            .artifact("hello", &platform("windows", "x86_64"), base)
+            .unwrap();
+        assert_eq!(windows.sha256.as_deref(), Some("any"));
+
+        let linux_only = LockedPlugin {
+            artifacts: locked.artifacts[..1].to_vec(),
+            ..locked.clone()
+        };
+        assert!(linux_only
+            .artifact("hello", &platform("macos", "aarch64"), base)
+            .is_err());
+    }
+}
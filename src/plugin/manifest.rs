@@ -0,0 +1,117 @@
+use anyhow::{Context, Result};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// What `cot install` has put under COT_HOME, kept in `$COT_HOME/installed.yaml`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct InstallManifest {
+    #[serde(default)]
+    pub plugins: BTreeMap<String, Vec<InstalledVersion>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstalledVersion {
+    pub version: Version,
+    pub source: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends: Vec<String>,
+    pub installed_at: u64,
+}
+
+pub fn manifest_path(home: &Path) -> PathBuf {
+    home.join("installed.yaml")
+}
+
+impl InstallManifest {
+    pub fn load(home: &Path) -> Result<InstallManifest> {
+        let path = manifest_path(home);
+        if !path.exists() {
+            return Ok(InstallManifest::default());
+        }
+        let data = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_yaml::from_str(&data).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    // write to a temporary file first, so a crash never leaves a truncated manifest
+    pub fn save(&self, home: &Path) -> Result<()> {
+        let path = manifest_path(home);
+        let data = serde_yaml::to_string(self)?;
+        let mut file = tempfile::NamedTempFile::new_in(home)?;
+        file.write_all(data.as_bytes())?;
+        file.persist(&path)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    pub fn versions(&self, name: &str) -> &[InstalledVersion] {
+        self.plugins.get(name).map(|v| v.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn get(&self, name: &str, version: &Version) -> Option<&InstalledVersion> {
+        self.versions(name).iter().find(|v| &v.version == version)
+    }
+
+    pub fn add(&mut self, name: &str, installed: InstalledVersion) {
+        let versions = self.plugins.entry(name.to_string()).or_default();
+        versions.retain(|v| v.version != installed.version);
+        versions.push(installed);
+        versions.sort_by(|a, b| a.version.cmp(&b.version));
+    }
+
+    pub fn remove(&mut self, name: &str, version: &Version) {
+        if let Some(versions) = self.plugins.get_mut(name) {
+            versions.retain(|v| &v.version != version);
+            if versions.is_empty() {
+                self.plugins.remove(name);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_roundtrip() {
+        let home = tempfile::tempdir().unwrap();
+        let mut manifest = InstallManifest::load(home.path()).unwrap();
+        assert!(manifest.plugins.is_empty());
+        for v in ["1.1.0", "1.0.0", "1.1.0"] {
+            manifest.add(
+                "node",
+                InstalledVersion {
+                    version: Version::parse(v).unwrap(),
+                    source: format!("node-{}.tar.gz", v),
+                    sha256: None,
+                    depends: vec![],
+                    installed_at: 0,
+                },
+            );
+        }
+        manifest.save(home.path()).unwrap();
+
+        let mut manifest = InstallManifest::load(home.path()).unwrap();
+        let versions: Vec<String> = manifest
+            .versions("node")
+            .iter()
+            .map(|v| v.version.to_string())
+            .collect();
+        assert_eq!(versions, vec!["1.0.0", "1.1.0"]);
+        assert!(manifest
+            .get("node", &Version::parse("1.0.0").unwrap())
+            .is_some());
+
+        for v in ["1.0.0", "1.1.0"] {
+            manifest.remove("node", &Version::parse(v).unwrap());
+        }
+        assert!(manifest.plugins.is_empty());
+    }
+}
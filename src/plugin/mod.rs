@@ -0,0 +1,40 @@
+mod active;
+mod cache;
+mod descriptor;
+mod env;
+mod exec;
+mod gc;
+mod home;
+mod hooks;
+mod index;
+mod install;
+mod list;
+mod lock;
+mod manifest;
+mod outdated;
+mod pins;
+mod projects;
+mod remove;
+mod self_update;
+mod shim;
+mod source;
+mod spec;
+mod upgrade;
+mod verify;
+
+pub use self::active::*;
+pub use self::cache::*;
+pub use self::env::*;
+pub use self::exec::*;
+pub use self::gc::*;
+pub use self::home::*;
+pub use self::install::*;
+pub use self::list::*;
+pub use self::lock::*;
+pub use self::outdated::*;
+pub use self::remove::*;
+pub use self::self_update::*;
+pub use self::shim::*;
+pub use self::source::*;
+pub use self::spec::*;
+pub use self::upgrade::*;
@@ -0,0 +1,136 @@
+use anyhow::Result;
+use log::warn;
+use semver::{Version, VersionReq};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use super::active::installed_match;
+use super::manifest::InstallManifest;
+use super::pins::{find_pin_file, PinFile};
+use super::source::Source;
+use crate::util::{version_gap, VersionGap};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OutdatedEntry {
+    pub name: String,
+    // highest installed version, or the one a pin selects
+    pub current: Version,
+    // newest version the pin allows, latest for installed plugins
+    pub wanted: Version,
+    pub latest: Version,
+    pub gap: VersionGap,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pin: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pinned_in: Option<PathBuf>,
+}
+
+// installed plugins, then the pins of the project around dir, that are behind the source
+pub fn outdated(home: &Path, source: &Source, dir: &Path) -> Result<Vec<OutdatedEntry>> {
+    let manifest = InstallManifest::load(home)?;
+    let mut entries = vec![];
+    for (name, versions) in &manifest.plugins {
+        let Some(current) = versions.iter().map(|v| &v.version).max() else {
+            continue;
+        };
+        let Some(latest) = latest(source, name, &VersionReq::STAR) else {
+            continue;
+        };
+        if let Some(gap) = version_gap(current, &latest) {
+            entries.push(OutdatedEntry {
+                name: name.clone(),
+                current: current.clone(),
+                wanted: latest.clone(),
+                latest,
+                gap,
+                pin: None,
+                pinned_in: None,
+            });
+        }
+    }
+
+    let Some(path) = find_pin_file(dir) else {
+        return Ok(entries);
+    };
+    let pin_file = PinFile::load(&path)?;
+    for pin in &pin_file.pins {
+        let req = pin.version_req()?;
+        let (Some(wanted), Some(latest)) = (
+            latest(source, &pin.name, &req),
+            latest(source, &pin.name, &VersionReq::STAR),
+        ) else {
+            continue;
+        };
+        let exact = pin.version.as_deref().and_then(|v| Version::parse(v).ok());
+        let current = match exact {
+            Some(version) => version,
+            None => installed_match(home, &pin.name, &req)?.unwrap_or(wanted.clone()),
+        };
+        // the source may no longer offer what is installed
+        let wanted = wanted.max(current.clone());
+        if let Some(gap) = version_gap(&current, &latest) {
+            entries.push(OutdatedEntry {
+                name: pin.name.clone(),
+                current,
+                wanted,
+                latest,
+                gap,
+                pin: Some(pin.to_string()),
+                pinned_in: Some(path.clone()),
+            });
+        }
+    }
+    Ok(entries)
+}
+
+fn latest(source: &Source, name: &str, req: &VersionReq) -> Option<Version> {
+    match source.locate(name, req) {
+        Ok(artifact) => Some(artifact.version),
+        Err(e) => {
+            warn!("skip {}: {}", name, e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::install::tests::make_archive;
+    use crate::plugin::install::{install, InstallOptions};
+    use crate::plugin::pins::{write_pin, PIN_FILE};
+    use crate::plugin::spec::PluginSpec;
+
+    #[test]
+    fn test_outdated() {
+        let home = tempfile::tempdir().unwrap();
+        let project = tempfile::tempdir().unwrap();
+        let source_dir = tempfile::tempdir().unwrap();
+        for version in ["1.0.0", "1.2.0", "2.0.0"] {
+            make_archive(source_dir.path(), "hello", version);
+        }
+        let source = Source::parse(source_dir.path().to_str().unwrap());
+        let spec = PluginSpec::parse("hello@1.0.0").unwrap();
+        install(home.path(), &source, &spec, &InstallOptions::default()).unwrap();
+        write_pin(
+            &project.path().join(PIN_FILE),
+            &PluginSpec::parse("hello@1").unwrap(),
+        )
+        .unwrap();
+
+        let entries = outdated(home.path(), &source, project.path()).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].gap, VersionGap::Major);
+        assert_eq!(entries[0].pin, None);
+        // the pin allows 1.2.0 but uses the installed 1.0.0
+        assert_eq!(entries[1].current, Version::parse("1.0.0").unwrap());
+        assert_eq!(entries[1].wanted, Version::parse("1.2.0").unwrap());
+        assert_eq!(entries[1].pin, Some("hello@1".to_string()));
+
+        let spec = PluginSpec::parse("hello@2.0.0").unwrap();
+        install(home.path(), &source, &spec, &InstallOptions::default()).unwrap();
+        let entries = outdated(home.path(), &source, project.path()).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].pinned_in.is_some());
+    }
+}
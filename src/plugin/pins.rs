@@ -0,0 +1,158 @@
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::spec::PluginSpec;
+
+pub const PIN_FILE: &str = ".cot-versions";
+
+/// A per-project `.cot-versions` file, one `plugin@version` per line, `#` starts a comment.
+/// The version may be a requirement such as `^20`, it selects the highest installed match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PinFile {
+    pub path: PathBuf,
+    pub pins: Vec<PluginSpec>,
+}
+
+impl PinFile {
+    pub fn load(path: &Path) -> Result<PinFile> {
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut pins = vec![];
+        for (n, line) in data.lines().enumerate() {
+            let Some(spec) = pin_of_line(line) else {
+                continue;
+            };
+            let spec =
+                PluginSpec::parse(spec).with_context(|| format!("{}:{}", path.display(), n + 1))?;
+            if spec.version.is_none() {
+                return Err(anyhow!(
+                    "{}:{}: {} has no version",
+                    path.display(),
+                    n + 1,
+                    spec.name
+                ));
+            }
+            pins.push(spec);
+        }
+        Ok(PinFile {
+            path: path.to_path_buf(),
+            pins,
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PluginSpec> {
+        self.pins.iter().find(|p| p.name == name)
+    }
+}
+
+fn pin_of_line(line: &str) -> Option<&str> {
+    let line = line.split('#').next().unwrap_or("").trim();
+    if line.is_empty() {
+        None
+    } else {
+        Some(line)
+    }
+}
+
+// nearest .cot-versions in dir or its parents, not looking above the git repository dir is in
+pub fn find_pin_file(dir: &Path) -> Option<PathBuf> {
+    for d in dir.ancestors() {
+        let path = d.join(PIN_FILE);
+        if path.is_file() {
+            return Some(path);
+        }
+        // a file in worktrees and submodules, no git process as this runs on every shim exec
+        if d.join(".git").exists() {
+            break;
+        }
+    }
+    None
+}
+
+// replace the line of spec.name, or append one, keeping comments and the other pins as they are
+pub fn write_pin(path: &Path, spec: &PluginSpec) -> Result<()> {
+    let data = if path.exists() {
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?
+    } else {
+        String::new()
+    };
+    let mut lines = vec![];
+    let mut replaced = false;
+    for line in data.lines() {
+        let same = pin_of_line(line)
+            .and_then(|pin| PluginSpec::parse(pin).ok())
+            .is_some_and(|pin| pin.name == spec.name);
+        if !same {
+            lines.push(line.to_string());
+        } else if !replaced {
+            lines.push(spec.to_string());
+            replaced = true;
+        }
+    }
+    if !replaced {
+        lines.push(spec.to_string());
+    }
+    fs::write(path, lines.join("\n") + "\n")
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PIN_FILE);
+        fs::write(&path, "# toolchain\nnode@18.0.0 # lts\n").unwrap();
+        write_pin(&path, &PluginSpec::parse("java@^21").unwrap()).unwrap();
+        write_pin(&path, &PluginSpec::parse("node@20.1.0").unwrap()).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# toolchain\nnode@20.1.0\njava@^21\n"
+        );
+        let pins = PinFile::load(&path).unwrap();
+        assert_eq!(pins.pins.len(), 2);
+        assert_eq!(pins.get("java").unwrap().version, Some("^21".to_string()));
+
+        let nested = dir.path().join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+        assert_eq!(find_pin_file(&nested), Some(path));
+    }
+
+    #[test]
+    fn test_find_pin_file_stops_at_repo() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(PIN_FILE), "node@20\n").unwrap();
+        let repo = dir.path().join("repo");
+        let nested = repo.join("src");
+        fs::create_dir_all(&nested).unwrap();
+        assert_eq!(find_pin_file(&nested), Some(dir.path().join(PIN_FILE)));
+
+        let status = std::process::Command::new("git")
+            .arg("init")
+            .arg("-q")
+            .arg(&repo)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        assert_eq!(find_pin_file(&nested), None);
+        fs::write(repo.join(PIN_FILE), "node@21\n").unwrap();
+        assert_eq!(find_pin_file(&nested), Some(repo.join(PIN_FILE)));
+
+        // worktrees and submodules have a .git file
+        let worktree = dir.path().join("worktree");
+        fs::create_dir(&worktree).unwrap();
+        fs::write(worktree.join(".git"), "gitdir: ../repo/.git\n").unwrap();
+        assert_eq!(find_pin_file(&worktree), None);
+    }
+
+    #[test]
+    fn test_pin_without_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PIN_FILE);
+        fs::write(&path, "node\n").unwrap();
+        assert!(PinFile::load(&path).is_err());
+    }
+}
@@ -0,0 +1,72 @@
+use anyhow::{Context, Result};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The `.cot-versions` files cot has worked with, kept in `$COT_HOME/projects.yaml`
+/// so `cot gc` knows which versions projects still need.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SeenProjects {
+    #[serde(default)]
+    pub pin_files: BTreeSet<PathBuf>,
+}
+
+pub fn projects_path(home: &Path) -> PathBuf {
+    home.join("projects.yaml")
+}
+
+impl SeenProjects {
+    pub fn load(home: &Path) -> Result<SeenProjects> {
+        let path = projects_path(home);
+        if !path.exists() {
+            return Ok(SeenProjects::default());
+        }
+        let data = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_yaml::from_str(&data).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn save(&self, home: &Path) -> Result<()> {
+        let path = projects_path(home);
+        let data = serde_yaml::to_string(self)?;
+        let mut file = tempfile::NamedTempFile::new_in(home)?;
+        file.write_all(data.as_bytes())?;
+        file.persist(&path)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+}
+
+// remember pin_file for gc, a no-op once it is known
+pub fn record_project(home: &Path, pin_file: &Path) -> Result<()> {
+    let pin_file = fs::canonicalize(pin_file).unwrap_or(pin_file.to_path_buf());
+    let mut projects = SeenProjects::load(home)?;
+    if projects.pin_files.insert(pin_file.clone()) {
+        debug!("seen project {}", pin_file.display());
+        projects.save(home)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_project() {
+        let home = tempfile::tempdir().unwrap();
+        let project = tempfile::tempdir().unwrap();
+        let pin_file = project.path().join(".cot-versions");
+        fs::write(&pin_file, "hello@1\n").unwrap();
+        record_project(home.path(), &pin_file).unwrap();
+        record_project(home.path(), &pin_file).unwrap();
+        let projects = SeenProjects::load(home.path()).unwrap();
+        assert_eq!(projects.pin_files.len(), 1);
+        assert!(projects
+            .pin_files
+            .contains(&fs::canonicalize(&pin_file).unwrap()));
+    }
+}
@@ -0,0 +1,299 @@
+use anyhow::{anyhow, Context, Result};
+use log::{debug, warn};
+use semver::Version;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::active::{active_version, current_link, global_version};
+use super::descriptor::PluginDescriptor;
+use super::home::{plugin_dir, version_dir};
+use super::hooks::{plugin_vars, run_hook, Hook};
+use super::manifest::InstallManifest;
+use super::shim::orphaned_shims;
+use super::spec::PluginSpec;
+
+/// What `cot remove` is going to delete.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemovalPlan {
+    pub name: String,
+    pub versions: Vec<Version>,
+    pub paths: Vec<PathBuf>,
+    // keep going when a pre-remove hook fails
+    pub force: bool,
+}
+
+// check everything up front, nothing is touched until remove() runs the plan
+pub fn plan_removal(
+    home: &Path,
+    spec: &PluginSpec,
+    all: bool,
+    force: bool,
+    dir: &Path,
+) -> Result<RemovalPlan> {
+    let manifest = InstallManifest::load(home)?;
+    let installed = manifest.versions(&spec.name);
+    if installed.is_empty() {
+        return Err(anyhow!("{} is not installed", spec.name));
+    }
+    let versions: Vec<Version> = match (&spec.version, all) {
+        (Some(_), _) => {
+            let req = spec.version_req()?;
+            installed
+                .iter()
+                .map(|v| v.version.clone())
+                .filter(|v| req.matches(v))
+                .collect()
+        }
+        (None, true) => installed.iter().map(|v| v.version.clone()).collect(),
+        (None, false) if installed.len() == 1 => vec![installed[0].version.clone()],
+        (None, false) => {
+            return Err(anyhow!(
+                "{} has {} versions installed, give one as {}@<version> or use --all",
+                spec.name,
+                installed.len(),
+                spec.name
+            ))
+        }
+    };
+    if versions.is_empty() {
+        return Err(anyhow!("no installed version of {} matches", spec));
+    }
+    let remaining: Vec<&Version> = installed
+        .iter()
+        .map(|v| &v.version)
+        .filter(|v| !versions.contains(v))
+        .collect();
+
+    if !force {
+        let global = global_version(home, &spec.name);
+        // a broken pin in dir is not a reason to refuse, only a resolvable one is
+        let here = active_version(home, &spec.name, dir).ok().flatten();
+        for active in [global, here.map(|a| a.version)].into_iter().flatten() {
+            if versions.contains(&active) {
+                return Err(anyhow!(
+                    "{}@{} is active, use --force to remove it anyway",
+                    spec.name,
+                    active
+                ));
+            }
+        }
+        for (other, other_versions) in &manifest.plugins {
+            if other == &spec.name {
+                continue;
+            }
+            for installed in other_versions {
+                for dependency in &installed.depends {
+                    let dependency = PluginSpec::parse(dependency)?;
+                    if dependency.name != spec.name {
+                        continue;
+                    }
+                    let req = dependency.version_req()?;
+                    if !remaining.iter().any(|v| req.matches(v)) {
+                        return Err(anyhow!(
+                            "{}@{} depends on {}, use --force to remove it anyway",
+                            other,
+                            installed.version,
+                            dependency
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    let mut paths: Vec<PathBuf> = versions
+        .iter()
+        .map(|v| version_dir(home, &spec.name, &v.to_string()))
+        .collect();
+    if remaining.is_empty() {
+        paths.push(plugin_dir(home, &spec.name));
+    } else if global_version(home, &spec.name).is_some_and(|v| versions.contains(&v)) {
+        paths.push(current_link(home, &spec.name));
+    }
+    // reshim drops the shims of binaries nothing else active provides
+    paths.extend(orphaned_shims(home, &spec.name, &versions)?);
+    Ok(RemovalPlan {
+        name: spec.name.clone(),
+        versions,
+        paths,
+        force,
+    })
+}
+
+// move everything into a trash directory first, so a failed manifest update can be undone
+pub fn remove(home: &Path, plan: &RemovalPlan) -> Result<()> {
+    for version in &plan.versions {
+        let dir = version_dir(home, &plan.name, &version.to_string());
+        let descriptor = PluginDescriptor::load(&dir)?;
+        let vars = plugin_vars(home, &plan.name, version, &dir);
+        if let Err(e) = run_hook(&descriptor, Hook::PreRemove, &dir, &vars) {
+            if !plan.force {
+                return Err(e.context("use --force to remove it anyway"));
+            }
+            warn!("{}@{}: {:?}", plan.name, version, e);
+        }
+    }
+    let trash = tempfile::Builder::new()
+        .prefix(".trash-")
+        .tempdir_in(home)?;
+    let mut moved: Vec<(PathBuf, PathBuf)> = vec![];
+    let result = (|| -> Result<()> {
+        for (i, path) in plan.paths.iter().enumerate() {
+            if path.symlink_metadata().is_err() {
+                continue;
+            }
+            let to = trash.path().join(i.to_string());
+            fs::rename(path, &to)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+            moved.push((path.clone(), to));
+        }
+        let mut manifest = InstallManifest::load(home)?;
+        for version in &plan.versions {
+            manifest.remove(&plan.name, version);
+        }
+        manifest.save(home)
+    })();
+    if let Err(e) = result {
+        for (path, to) in moved.iter().rev() {
+            if let Err(e) = fs::rename(to, path) {
+                warn!("Failed to restore {}: {:?}", path.display(), e);
+            }
+        }
+        return Err(e);
+    }
+    debug!("removed {:?}", plan.paths);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::active::set_global;
+    use crate::plugin::home::shims_dir;
+    use crate::plugin::install::tests::{make_archive, make_archive_with};
+    use crate::plugin::install::{install, InstallOptions};
+    use crate::plugin::shim::reshim;
+    use crate::plugin::source::Source;
+
+    fn setup(versions: &[&str]) -> (tempfile::TempDir, tempfile::TempDir) {
+        let home = tempfile::tempdir().unwrap();
+        let source_dir = tempfile::tempdir().unwrap();
+        let source = Source::parse(source_dir.path().to_str().unwrap());
+        for version in versions {
+            make_archive(source_dir.path(), "hello", version);
+            let spec = PluginSpec::parse(&format!("hello@{}", version)).unwrap();
+            install(home.path(), &source, &spec, &InstallOptions::default()).unwrap();
+        }
+        (home, source_dir)
+    }
+
+    #[test]
+    fn test_remove_one() {
+        let (home, _source) = setup(&["1.0.0", "2.0.0"]);
+        let dir = home.path();
+        set_global(dir, "hello", &Version::parse("2.0.0").unwrap()).unwrap();
+        let spec = PluginSpec::parse("hello").unwrap();
+        assert!(plan_removal(dir, &spec, false, false, dir).is_err());
+
+        let spec = PluginSpec::parse("hello@2.0.0").unwrap();
+        assert!(plan_removal(dir, &spec, false, false, dir).is_err());
+
+        let spec = PluginSpec::parse("hello@1.0.0").unwrap();
+        let plan = plan_removal(dir, &spec, false, false, dir).unwrap();
+        assert_eq!(plan.paths, vec![version_dir(dir, "hello", "1.0.0")]);
+        remove(dir, &plan).unwrap();
+        assert!(!version_dir(dir, "hello", "1.0.0").exists());
+        assert!(version_dir(dir, "hello", "2.0.0").exists());
+        assert_eq!(
+            InstallManifest::load(dir).unwrap().versions("hello").len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_remove_all_forced() {
+        let (home, _source) = setup(&["1.0.0", "2.0.0"]);
+        let dir = home.path();
+        set_global(dir, "hello", &Version::parse("2.0.0").unwrap()).unwrap();
+        let spec = PluginSpec::parse("hello").unwrap();
+        assert!(plan_removal(dir, &spec, true, false, dir).is_err());
+        let plan = plan_removal(dir, &spec, true, true, dir).unwrap();
+        assert!(plan.paths.contains(&plugin_dir(dir, "hello")));
+        remove(dir, &plan).unwrap();
+        assert!(!plugin_dir(dir, "hello").exists());
+        assert!(InstallManifest::load(dir).unwrap().plugins.is_empty());
+        // trash is gone too
+        assert_eq!(fs::read_dir(dir).unwrap().count(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_remove_shims() {
+        let (home, _source) = setup(&["1.0.0", "2.0.0"]);
+        let dir = home.path();
+        let cot = Path::new("/usr/local/bin/cot");
+        set_global(dir, "hello", &Version::parse("2.0.0").unwrap()).unwrap();
+        reshim(dir, cot).unwrap();
+        let shim = shims_dir(dir).join("hello");
+        assert!(shim.is_file());
+
+        // 2.0.0 still provides hello
+        let spec = PluginSpec::parse("hello@1.0.0").unwrap();
+        let plan = plan_removal(dir, &spec, false, false, dir).unwrap();
+        assert!(!plan.paths.contains(&shim));
+
+        let spec = PluginSpec::parse("hello").unwrap();
+        let plan = plan_removal(dir, &spec, true, true, dir).unwrap();
+        assert_eq!(plan.paths.last(), Some(&shim));
+        remove(dir, &plan).unwrap();
+        assert!(!shim.exists());
+        assert!(reshim(dir, cot).unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_remove_hook() {
+        let home = tempfile::tempdir().unwrap();
+        let dir = home.path();
+        let source_dir = tempfile::tempdir().unwrap();
+        let source = Source::parse(source_dir.path().to_str().unwrap());
+        make_archive_with(
+            source_dir.path(),
+            "hooked",
+            "1.0.0",
+            Some("hooks:\n  pre-remove: test -f $COT_HOME/allow\n"),
+        );
+        install(
+            dir,
+            &source,
+            &PluginSpec::parse("hooked").unwrap(),
+            &InstallOptions::default(),
+        )
+        .unwrap();
+
+        let spec = PluginSpec::parse("hooked").unwrap();
+        let plan = plan_removal(dir, &spec, false, false, dir).unwrap();
+        assert!(remove(dir, &plan).is_err());
+        assert!(version_dir(dir, "hooked", "1.0.0").exists());
+
+        let plan = plan_removal(dir, &spec, false, true, dir).unwrap();
+        remove(dir, &plan).unwrap();
+        assert!(!version_dir(dir, "hooked", "1.0.0").exists());
+    }
+
+    #[test]
+    fn test_remove_dependency() {
+        let (home, _source) = setup(&["1.0.0"]);
+        let dir = home.path();
+        let mut manifest = InstallManifest::load(dir).unwrap();
+        let mut app = manifest.versions("hello")[0].clone();
+        app.depends = vec!["hello@^1".to_string()];
+        manifest.add("app", app);
+        manifest.save(dir).unwrap();
+
+        let spec = PluginSpec::parse("hello").unwrap();
+        let e = plan_removal(dir, &spec, false, false, dir).unwrap_err();
+        assert!(e.to_string().contains("app@1.0.0 depends on hello@^1"));
+        assert!(plan_removal(dir, &spec, false, true, dir).is_ok());
+    }
+}
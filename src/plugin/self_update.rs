@@ -0,0 +1,165 @@
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use semver::Version;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::install::unpack;
+use super::source::{archive_extension, read_signature, split_archive_name, Artifact};
+use super::verify::{public_key, verify_artifact};
+
+const BINARY: &str = if cfg!(windows) { "cot.exe" } else { "cot" };
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelfUpdate {
+    pub from: Version,
+    pub to: Version,
+    pub path: PathBuf,
+    // false when the release is the running version
+    pub replaced: bool,
+}
+
+// `cot self-update`: replace exe with the cot of a release archive, or a bare cot binary
+pub fn self_update(home: &Path, release: &str, exe: &Path, force: bool) -> Result<SelfUpdate> {
+    let release = PathBuf::from(release.strip_prefix("file://").unwrap_or(release));
+    if !release.is_file() {
+        return Err(anyhow!("release {} is not a file", release.display()));
+    }
+    let file_name = release
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let from = Version::parse(env!("CARGO_PKG_VERSION"))?;
+    let artifact = Artifact {
+        name: "cot".to_string(),
+        version: split_archive_name(&file_name)
+            .map(|(_, v)| v)
+            .unwrap_or(from.clone()),
+        url: release.to_string_lossy().to_string(),
+        sha256: None,
+        signature: read_signature(&release),
+        depends: vec![],
+    };
+    verify_artifact(&artifact, &release, public_key(home)?.as_ref())?;
+
+    // next to exe, so putting it in place is a rename
+    let exe_dir = exe.parent().unwrap_or(Path::new("."));
+    let staging = tempfile::Builder::new()
+        .prefix(".cot-update-")
+        .tempdir_in(exe_dir)
+        .with_context(|| format!("Failed to write to {}", exe_dir.display()))?;
+    let binary = if archive_extension(&file_name).is_some() {
+        let unpacked = staging.path().join("unpacked");
+        fs::create_dir(&unpacked)?;
+        unpack(&release, &unpacked)?;
+        find_binary(&unpacked)?.ok_or_else(|| anyhow!("no {} in {}", BINARY, release.display()))?
+    } else {
+        let binary = staging.path().join(BINARY);
+        fs::copy(&release, &binary)?;
+        binary
+    };
+    make_executable(&binary)?;
+
+    let to = binary_version(&binary)?;
+    if to == from && !force {
+        return Ok(SelfUpdate {
+            from,
+            to,
+            path: exe.to_path_buf(),
+            replaced: false,
+        });
+    }
+    if to < from && !force {
+        return Err(anyhow!(
+            "{} is cot {}, older than the running {}, use --force to downgrade",
+            release.display(),
+            to,
+            from
+        ));
+    }
+
+    // windows can't replace a running exe, but can rename it
+    if cfg!(windows) {
+        let old = exe.with_extension("old");
+        let _ = fs::remove_file(&old);
+        fs::rename(exe, &old)?;
+    }
+    fs::rename(&binary, exe).with_context(|| format!("Failed to replace {}", exe.display()))?;
+    debug!("{} replaced by cot {}", exe.display(), to);
+    Ok(SelfUpdate {
+        from,
+        to,
+        path: exe.to_path_buf(),
+        replaced: true,
+    })
+}
+
+fn find_binary(dir: &Path) -> Result<Option<PathBuf>> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if let Some(found) = find_binary(&path)? {
+                return Ok(Some(found));
+            }
+        } else if path.file_name().is_some_and(|n| n == BINARY) {
+            return Ok(Some(path));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+// `cot --version` prints "cot 0.2.0"
+fn binary_version(binary: &Path) -> Result<Version> {
+    let output = Command::new(binary)
+        .arg("--version")
+        .output()
+        .with_context(|| format!("Failed to run {}", binary.display()))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .trim()
+        .strip_prefix("cot ")
+        .filter(|_| output.status.success())
+        .and_then(|v| Version::parse(v).ok())
+        .ok_or_else(|| anyhow!("{} is not a cot binary", binary.display()))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::plugin::install::tests::make_archive;
+
+    #[test]
+    fn test_self_update() {
+        let home = tempfile::tempdir().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let exe = dir.path().join("cot");
+        fs::write(&exe, "old").unwrap();
+
+        let release = make_archive(dir.path(), "cot", "999.0.0");
+        let update = self_update(home.path(), release.to_str().unwrap(), &exe, false).unwrap();
+        assert!(update.replaced);
+        assert_eq!(update.to, Version::parse("999.0.0").unwrap());
+        assert_eq!(binary_version(&exe).unwrap(), update.to);
+
+        let older = make_archive(dir.path(), "cot", "0.0.1");
+        let e = self_update(home.path(), older.to_str().unwrap(), &exe, false).unwrap_err();
+        assert!(e.to_string().contains("use --force to downgrade"));
+
+        let other = make_archive(dir.path(), "hello", "1.0.0");
+        assert!(self_update(home.path(), other.to_str().unwrap(), &exe, true).is_err());
+    }
+}
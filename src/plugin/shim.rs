@@ -0,0 +1,350 @@
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use semver::Version;
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::active::{active_version, global_version, installed_match, Active, Origin};
+use super::descriptor::PluginDescriptor;
+use super::home::{shims_dir, version_dir};
+use super::hooks::{plugin_env, plugin_vars};
+use super::manifest::InstallManifest;
+use super::pins::PinFile;
+use super::projects::SeenProjects;
+
+// rewrite $COT_HOME/shims with one shim per binary of the active plugins,
+// each shim asks `cot shim` which version is active where it runs
+pub fn reshim(home: &Path, cot: &Path) -> Result<Vec<String>> {
+    let binaries = shimmed_binaries(home, &|_, _| false)?;
+    let shims = shims_dir(home);
+    if shims.exists() {
+        fs::remove_dir_all(&shims)
+            .with_context(|| format!("Failed to clean {}", shims.display()))?;
+    }
+    fs::create_dir_all(&shims)?;
+    for binary in &binaries {
+        let (file_name, script) = shim_script(cot, binary);
+        let path = shims.join(file_name);
+        fs::write(&path, script).with_context(|| format!("Failed to write {}", path.display()))?;
+        make_executable(&path)?;
+    }
+    debug!("{} shims in {}", binaries.len(), shims.display());
+    Ok(binaries.into_iter().collect())
+}
+
+// binaries of the active plugins, leaving out the versions skip says are going away
+fn shimmed_binaries(
+    home: &Path,
+    skip: &dyn Fn(&str, &Version) -> bool,
+) -> Result<BTreeSet<String>> {
+    let mut binaries = BTreeSet::new();
+    for (name, versions) in activated_versions(home)? {
+        for version in versions.iter().filter(|v| !skip(&name, v)) {
+            let dir = version_dir(home, &name, &version.to_string());
+            let descriptor = PluginDescriptor::load(&dir)?;
+            for (binary, _) in descriptor.binaries(&dir)? {
+                binaries.insert(binary);
+            }
+        }
+    }
+    Ok(binaries)
+}
+
+// the shims reshim deletes once versions of name are removed
+pub fn orphaned_shims(home: &Path, name: &str, versions: &[Version]) -> Result<Vec<PathBuf>> {
+    let shims = shims_dir(home);
+    if !shims.is_dir() {
+        return Ok(vec![]);
+    }
+    let kept: BTreeSet<String> = shimmed_binaries(home, &|n, v| n == name && versions.contains(v))?
+        .iter()
+        .map(|binary| shim_name(binary))
+        .collect();
+    let mut orphaned = vec![];
+    for entry in fs::read_dir(&shims)? {
+        let entry = entry?;
+        if !kept.contains(entry.file_name().to_string_lossy().as_ref()) {
+            orphaned.push(entry.path());
+        }
+    }
+    orphaned.sort();
+    Ok(orphaned)
+}
+
+// the global versions and what the seen projects pin, installed versions nothing selects get no shims
+fn activated_versions(home: &Path) -> Result<BTreeMap<String, BTreeSet<Version>>> {
+    let manifest = InstallManifest::load(home)?;
+    let mut activated: BTreeMap<String, BTreeSet<Version>> = BTreeMap::new();
+    for name in manifest.plugins.keys() {
+        if let Some(version) = global_version(home, name) {
+            activated.entry(name.clone()).or_default().insert(version);
+        }
+    }
+    for pin_file in SeenProjects::load(home)?.pin_files {
+        let pins = match PinFile::load(&pin_file) {
+            Ok(pins) => pins,
+            Err(e) => {
+                debug!("{:?}", e);
+                continue;
+            }
+        };
+        for pin in &pins.pins {
+            if let Some(version) = installed_match(home, &pin.name, &pin.version_req()?)? {
+                activated
+                    .entry(pin.name.clone())
+                    .or_default()
+                    .insert(version);
+            }
+        }
+    }
+    Ok(activated)
+}
+
+#[cfg(unix)]
+fn shim_name(binary: &str) -> String {
+    binary.to_string()
+}
+
+#[cfg(not(unix))]
+fn shim_name(binary: &str) -> String {
+    format!("{}.cmd", binary)
+}
+
+#[cfg(unix)]
+fn shim_script(cot: &Path, binary: &str) -> (String, String) {
+    (
+        shim_name(binary),
+        format!(
+            "#!/bin/sh\n# cot shim, regenerate with `cot reshim`\nexec \"{}\" shim \"{}\" \"$@\"\n",
+            cot.display(),
+            binary
+        ),
+    )
+}
+
+#[cfg(not(unix))]
+fn shim_script(cot: &Path, binary: &str) -> (String, String) {
+    (
+        shim_name(binary),
+        format!(
+            "@echo off\r\nrem cot shim, regenerate with `cot reshim`\r\n\"{}\" shim \"{}\" %*\r\n",
+            cot.display(),
+            binary
+        ),
+    )
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// The executable a shim runs, and the plugin version it comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedBinary {
+    pub active: Active,
+    pub path: PathBuf,
+    pub bin_dirs: Vec<PathBuf>,
+    // the env the plugin declares
+    pub env: Vec<(String, String)>,
+}
+
+// plugins pinned by the project in dir win over globally active ones
+pub fn resolve_binary(home: &Path, binary: &str, dir: &Path) -> Result<ResolvedBinary> {
+    lookup_binary(home, binary, dir)?.ok_or_else(|| not_active(binary))
+}
+
+fn not_active(binary: &str) -> anyhow::Error {
+    anyhow!(
+        "no active plugin provides {}, select one with `cot use`",
+        binary
+    )
+}
+
+// none when no plugin is active for binary in dir, an error when a pin there can't be met
+fn lookup_binary(home: &Path, binary: &str, dir: &Path) -> Result<Option<ResolvedBinary>> {
+    let manifest = InstallManifest::load(home)?;
+    let mut candidates = vec![];
+    let mut pin_error = None;
+    for name in manifest.plugins.keys() {
+        match active_version(home, name, dir) {
+            Ok(Some(active)) => candidates.push(active),
+            Ok(None) => {}
+            Err(e) => pin_error = Some(e),
+        }
+    }
+    candidates.sort_by_key(|a| matches!(a.origin, Origin::Global));
+    for active in candidates {
+        let plugin_dir = version_dir(home, &active.name, &active.version.to_string());
+        let descriptor = PluginDescriptor::load(&plugin_dir)?;
+        let found = descriptor
+            .binaries(&plugin_dir)?
+            .into_iter()
+            .find(|(name, _)| name == binary);
+        if let Some((_, path)) = found {
+            let vars = plugin_vars(home, &active.name, &active.version, &plugin_dir);
+            return Ok(Some(ResolvedBinary {
+                bin_dirs: descriptor.bin_dirs(&plugin_dir),
+                env: plugin_env(&descriptor, &vars),
+                active,
+                path,
+            }));
+        }
+    }
+    match pin_error {
+        Some(e) => Err(e.context(format!("no active plugin provides {}", binary))),
+        None => Ok(None),
+    }
+}
+
+// the binary the shim hides, next on path without $COT_HOME/shims
+fn system_binary(home: &Path, binary: &str, path: &OsStr) -> Option<PathBuf> {
+    let shims = shims_dir(home);
+    let shims = fs::canonicalize(&shims).unwrap_or(shims);
+    env::split_paths(path)
+        .filter(|dir| fs::canonicalize(dir).unwrap_or(dir.clone()) != shims)
+        .flat_map(|dir| executable_names(binary).map(move |name| dir.join(name)))
+        .find(|path| path.is_file())
+}
+
+#[cfg(unix)]
+fn executable_names(binary: &str) -> impl Iterator<Item = String> {
+    std::iter::once(binary.to_string())
+}
+
+#[cfg(not(unix))]
+fn executable_names(binary: &str) -> impl Iterator<Item = String> {
+    let binary = binary.to_string();
+    ["exe", "cmd", "bat"]
+        .into_iter()
+        .map(move |ext| format!("{}.{}", binary, ext))
+}
+
+// PATH with dirs in front
+pub fn path_with(dirs: &[PathBuf]) -> Result<OsString> {
+    let current = env::var_os("PATH").unwrap_or_default();
+    let paths = dirs.iter().cloned().chain(env::split_paths(&current));
+    Ok(env::join_paths(paths)?)
+}
+
+// replace this process with the resolved binary, or wait for it where that isn't possible
+pub fn exec(mut command: Command) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        let e = command.exec();
+        Err(anyhow!("Failed to run {:?}: {}", command.get_program(), e))
+    }
+    #[cfg(not(unix))]
+    {
+        let status = command
+            .status()
+            .with_context(|| format!("Failed to run {:?}", command.get_program()))?;
+        std::process::exit(status.code().unwrap_or(1));
+    }
+}
+
+pub fn run_shim(home: &Path, binary: &str, args: &[String], dir: &Path) -> Result<()> {
+    let Some(resolved) = lookup_binary(home, binary, dir)? else {
+        // active somewhere else, here the shim must not hide the system's binary
+        let path = env::var_os("PATH").unwrap_or_default();
+        let path = system_binary(home, binary, &path).ok_or_else(|| not_active(binary))?;
+        debug!("shim {} => {}", binary, path.display());
+        let mut command = Command::new(path);
+        command.args(args);
+        return exec(command);
+    };
+    debug!(
+        "shim {} => {}@{} {}",
+        binary,
+        resolved.active.name,
+        resolved.active.version,
+        resolved.path.display()
+    );
+    let mut command = Command::new(&resolved.path);
+    command
+        .args(args)
+        .envs(resolved.env)
+        .env("PATH", path_with(&resolved.bin_dirs)?);
+    exec(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::active::set_global;
+    use crate::plugin::install::tests::make_archive;
+    use crate::plugin::install::{install, InstallOptions};
+    use crate::plugin::pins::{write_pin, PIN_FILE};
+    use crate::plugin::source::Source;
+    use crate::plugin::spec::PluginSpec;
+    use semver::Version;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_reshim_and_resolve() {
+        let home = tempfile::tempdir().unwrap();
+        let source_dir = tempfile::tempdir().unwrap();
+        make_archive(source_dir.path(), "hello", "1.0.0");
+        make_archive(source_dir.path(), "hello", "2.0.0");
+        let source = Source::parse(source_dir.path().to_str().unwrap());
+        for spec in ["hello@1.0.0", "hello@2.0.0"] {
+            let spec = PluginSpec::parse(spec).unwrap();
+            install(home.path(), &source, &spec, &InstallOptions::default()).unwrap();
+        }
+        let project = tempfile::tempdir().unwrap();
+        assert!(resolve_binary(home.path(), "hello", project.path()).is_err());
+
+        // installed but not selected, no shim hides the system's hello
+        let cot = Path::new("/usr/local/bin/cot");
+        assert!(reshim(home.path(), cot).unwrap().is_empty());
+
+        set_global(home.path(), "hello", &Version::parse("2.0.0").unwrap()).unwrap();
+        let shims = reshim(home.path(), cot).unwrap();
+        assert_eq!(shims, vec!["hello"]);
+        let (file_name, _) = shim_script(Path::new("cot"), "hello");
+        assert!(shims_dir(home.path()).join(file_name).is_file());
+        let resolved = resolve_binary(home.path(), "hello", project.path()).unwrap();
+        assert_eq!(resolved.active.version, Version::parse("2.0.0").unwrap());
+        assert_eq!(
+            resolved.path,
+            version_dir(home.path(), "hello", "2.0.0")
+                .join("bin")
+                .join("hello")
+        );
+
+        let pin = PluginSpec::parse("hello@1.0.0").unwrap();
+        write_pin(&project.path().join(PIN_FILE), &pin).unwrap();
+        let resolved = resolve_binary(home.path(), "hello", project.path()).unwrap();
+        assert_eq!(resolved.active.version, Version::parse("1.0.0").unwrap());
+        assert!(resolve_binary(home.path(), "world", project.path()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_system_binary() {
+        let home = tempfile::tempdir().unwrap();
+        let system = tempfile::tempdir().unwrap();
+        let shims = shims_dir(home.path());
+        fs::create_dir_all(&shims).unwrap();
+        fs::write(shims.join("hello"), "").unwrap();
+        fs::write(system.path().join("hello"), "").unwrap();
+        let path = env::join_paths([shims, system.path().to_path_buf()]).unwrap();
+        assert_eq!(
+            system_binary(home.path(), "hello", &path),
+            Some(system.path().join("hello"))
+        );
+        assert_eq!(system_binary(home.path(), "world", &path), None);
+    }
+}
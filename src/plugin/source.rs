@@ -0,0 +1,323 @@
+use anyhow::{anyhow, Context, Result};
+use semver::{Version, VersionReq};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::index::{artifact_url, is_remote, Platform, PluginIndex, INDEX_FILE};
+
+pub const ARCHIVE_EXTENSIONS: [&str; 4] = [".tar.gz", ".tgz", ".tar", ".zip"];
+
+/// Where plugin archives come from: a local directory given as a path or a `file://` url.
+/// If it is a yaml file, or a directory with an `index.yaml`, versions are resolved through
+/// that [`PluginIndex`]; otherwise the directory holds `<name>-<version>.<ext>` archives
+/// either at the top level or under `<name>/`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Source {
+    pub root: PathBuf,
+}
+
+/// An archive picked from a source for one plugin version.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Artifact {
+    pub name: String,
+    pub version: Version,
+    // a local path, or an http(s) url
+    pub url: String,
+    pub sha256: Option<String>,
+    // minisign signature of the archive
+    pub signature: Option<String>,
+    pub depends: Vec<String>,
+}
+
+impl Artifact {
+    pub fn local_path(&self) -> Option<PathBuf> {
+        if is_remote(&self.url) {
+            None
+        } else {
+            Some(PathBuf::from(&self.url))
+        }
+    }
+
+    // node-20.1.0.tar.gz of .../node-20.1.0.tar.gz?download=1
+    pub fn file_name(&self) -> String {
+        let url = self.url.split(['?', '#']).next().unwrap_or("");
+        url.rsplit(['/', '\\']).next().unwrap_or(url).to_string()
+    }
+}
+
+impl Source {
+    pub fn parse(location: &str) -> Source {
+        let path = PathBuf::from(location.strip_prefix("file://").unwrap_or(location));
+        // urls end up in installed.yaml and lockfiles, which are read from other directories
+        Source {
+            root: std::path::absolute(&path).unwrap_or(path),
+        }
+    }
+
+    // --source, otherwise COT_SOURCE, otherwise $COT_HOME/source
+    pub fn from_env(source: Option<&String>, home: &Path) -> Source {
+        match source {
+            Some(s) => Source::parse(s),
+            None => match env::var("COT_SOURCE") {
+                Ok(s) if !s.is_empty() => Source::parse(&s),
+                _ => Source {
+                    root: home.join("source"),
+                },
+            },
+        }
+    }
+
+    pub fn index_path(&self) -> Option<PathBuf> {
+        if self.root.is_file() {
+            return Some(self.root.clone());
+        }
+        let index = self.root.join(INDEX_FILE);
+        if index.is_file() {
+            return Some(index);
+        }
+        None
+    }
+
+    // what relative urls of the source are relative to, the index's directory or the root
+    pub fn base_dir(&self) -> PathBuf {
+        match self.index_path() {
+            Some(index) => index.parent().unwrap_or(Path::new(".")).to_path_buf(),
+            None => self.root.clone(),
+        }
+    }
+
+    pub fn artifacts(&self, name: &str) -> Result<Vec<Artifact>> {
+        if !self.root.is_dir() {
+            return Err(anyhow!(
+                "plugin source {} is not a directory",
+                self.root.display()
+            ));
+        }
+        let mut artifacts = vec![];
+        for dir in [self.root.clone(), self.root.join(name)] {
+            if !dir.is_dir() {
+                continue;
+            }
+            let entries =
+                fs::read_dir(&dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+            for entry in entries {
+                let path = entry?.path();
+                if !path.is_file() {
+                    continue;
+                }
+                let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+                if let Some(version) = archive_version(name, &file_name) {
+                    artifacts.push(Artifact {
+                        name: name.to_string(),
+                        version,
+                        url: path.to_string_lossy().to_string(),
+                        sha256: None,
+                        signature: read_signature(&path),
+                        depends: vec![],
+                    });
+                }
+            }
+        }
+        artifacts.sort_by(|a, b| a.version.cmp(&b.version));
+        Ok(artifacts)
+    }
+
+    // highest version satisfying req, for the current platform if there is an index
+    pub fn locate(&self, name: &str, req: &VersionReq) -> Result<Artifact> {
+        if let Some(index_path) = self.index_path() {
+            let index = PluginIndex::load(&index_path)?;
+            let resolved = index.resolve(name, req, &Platform::current())?;
+            let index_dir = index_path.parent().unwrap_or(Path::new("."));
+            return Ok(Artifact {
+                name: resolved.name,
+                version: resolved.version,
+                url: artifact_url(index_dir, &resolved.artifact.url),
+                sha256: Some(resolved.artifact.sha256),
+                signature: resolved.artifact.signature,
+                depends: resolved.depends,
+            });
+        }
+        self.artifacts(name)?
+            .into_iter()
+            .rfind(|a| req.matches(&a.version))
+            .ok_or_else(|| {
+                anyhow!(
+                    "no archive of {} matching {} in {}",
+                    name,
+                    req,
+                    self.root.display()
+                )
+            })
+    }
+
+    // every plugin the source offers for the current platform, with its versions ascending
+    pub fn available(&self) -> Result<BTreeMap<String, Vec<Version>>> {
+        let mut available: BTreeMap<String, Vec<Version>> = BTreeMap::new();
+        if let Some(index_path) = self.index_path() {
+            let index = PluginIndex::load(&index_path)?;
+            let platform = Platform::current();
+            for name in index.plugins.keys() {
+                let versions = index.versions(name, &platform);
+                if !versions.is_empty() {
+                    available.insert(name.clone(), versions);
+                }
+            }
+            return Ok(available);
+        }
+        if !self.root.is_dir() {
+            return Err(anyhow!(
+                "plugin source {} is not a directory",
+                self.root.display()
+            ));
+        }
+        let mut files = vec![];
+        for entry in fs::read_dir(&self.root)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                for sub in fs::read_dir(&path)? {
+                    files.push(sub?.path());
+                }
+            } else {
+                files.push(path);
+            }
+        }
+        for path in files.into_iter().filter(|p| p.is_file()) {
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            if let Some((name, version)) = split_archive_name(&file_name) {
+                available.entry(name).or_default().push(version);
+            }
+        }
+        for versions in available.values_mut() {
+            versions.sort();
+            versions.dedup();
+        }
+        Ok(available)
+    }
+}
+
+pub fn archive_extension(file_name: &str) -> Option<&'static str> {
+    ARCHIVE_EXTENSIONS
+        .into_iter()
+        .find(|ext| file_name.ends_with(ext))
+}
+
+// node-20.1.0.tar.gz => 20.1.0
+fn archive_version(name: &str, file_name: &str) -> Option<Version> {
+    let ext = archive_extension(file_name)?;
+    let stem = file_name.strip_suffix(ext)?;
+    let version = stem.strip_prefix(name)?.strip_prefix('-')?;
+    Version::parse(version.trim_start_matches('v')).ok()
+}
+
+// node-tools-20.1.0-rc.1.zip => (node-tools, 20.1.0-rc.1)
+pub fn split_archive_name(file_name: &str) -> Option<(String, Version)> {
+    let ext = archive_extension(file_name)?;
+    let stem = file_name.strip_suffix(ext)?;
+    stem.match_indices('-').find_map(|(i, _)| {
+        let name = &stem[..i];
+        archive_version(name, file_name).map(|v| (name.to_string(), v))
+    })
+}
+
+// hello-1.0.0.tar.gz.minisig next to hello-1.0.0.tar.gz
+pub fn read_signature(archive: &Path) -> Option<String> {
+    let mut path = archive.as_os_str().to_owned();
+    path.push(".minisig");
+    fs::read_to_string(path).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_version() {
+        assert_eq!(
+            archive_version("node", "node-20.1.0.tar.gz"),
+            Some(Version::parse("20.1.0").unwrap())
+        );
+        assert_eq!(
+            archive_version("node", "node-v1.0.0-rc.1.zip"),
+            Some(Version::parse("1.0.0-rc.1").unwrap())
+        );
+        assert_eq!(archive_version("node", "nodejs-20.1.0.tar.gz"), None);
+        assert_eq!(archive_version("node", "node-20.1.0.rar"), None);
+    }
+
+    #[test]
+    fn test_split_archive_name() {
+        assert_eq!(
+            split_archive_name("node-tools-v20.1.0-rc.1.zip"),
+            Some((
+                "node-tools".to_string(),
+                Version::parse("20.1.0-rc.1").unwrap()
+            ))
+        );
+        assert_eq!(split_archive_name("README.md"), None);
+    }
+
+    #[test]
+    fn test_available() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("node")).unwrap();
+        for file in [
+            "node-1.0.0.tar.gz",
+            "node/node-1.2.0.zip",
+            "java-21.0.0.tgz",
+        ] {
+            fs::write(dir.path().join(file), b"").unwrap();
+        }
+        let available = Source::parse(dir.path().to_str().unwrap())
+            .available()
+            .unwrap();
+        assert_eq!(available.keys().collect::<Vec<_>>(), vec!["java", "node"]);
+        assert_eq!(available["node"].len(), 2);
+    }
+
+    #[test]
+    fn test_locate() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("node")).unwrap();
+        for file in ["node-1.0.0.tar.gz", "node/node-1.2.0.zip", "node-2.0.0.tgz"] {
+            fs::write(dir.path().join(file), b"").unwrap();
+        }
+        let source = Source::parse(&format!("file://{}", dir.path().display()));
+        fs::write(dir.path().join("node-2.0.0.tgz.minisig"), "sig").unwrap();
+        let latest = source.locate("node", &VersionReq::STAR).unwrap();
+        assert_eq!(latest.version, Version::parse("2.0.0").unwrap());
+        assert_eq!(latest.signature, Some("sig".to_string()));
+        let one = source
+            .locate("node", &VersionReq::parse("^1").unwrap())
+            .unwrap();
+        assert_eq!(
+            one.local_path(),
+            Some(dir.path().join("node").join("node-1.2.0.zip"))
+        );
+        assert!(source
+            .locate("node", &VersionReq::parse("^3").unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn test_locate_with_index() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join(INDEX_FILE),
+            "version: 1\nplugins:\n  node:\n    releases:\n      - version: 1.0.0\n        artifacts:\n          - url: archives/node.zip\n            sha256: abc\n",
+        )
+        .unwrap();
+        let source = Source::parse(dir.path().to_str().unwrap());
+        let artifact = source.locate("node", &VersionReq::STAR).unwrap();
+        assert_eq!(artifact.version, Version::parse("1.0.0").unwrap());
+        assert_eq!(
+            artifact.local_path(),
+            Some(dir.path().join("archives").join("node.zip"))
+        );
+        assert_eq!(artifact.file_name(), "node.zip");
+        assert_eq!(artifact.sha256, Some("abc".to_string()));
+        let source = Source::parse(dir.path().join(INDEX_FILE).to_str().unwrap());
+        assert_eq!(source.locate("node", &VersionReq::STAR).unwrap(), artifact);
+    }
+}
@@ -0,0 +1,90 @@
+use anyhow::{anyhow, Result};
+use semver::{Version, VersionReq};
+use std::fmt;
+
+/// `<plugin>[@version]` as given on the command line.
+/// A full version such as `1.2.3` means exactly that version,
+/// anything else is parsed as a semver requirement (`^1.2`, `>=1, <2`, ...).
+#[derive(Debug, Clone, PartialEq)]
+pub struct PluginSpec {
+    pub name: String,
+    pub version: Option<String>,
+}
+
+impl PluginSpec {
+    pub fn parse(spec: &str) -> Result<PluginSpec> {
+        let (name, version) = match spec.split_once('@') {
+            Some((name, version)) => (name.trim(), Some(version.trim())),
+            None => (spec.trim(), None),
+        };
+        if name.is_empty() {
+            return Err(anyhow!("plugin name is required in {:?}", spec));
+        }
+        if name.contains(['/', '\\']) || name.starts_with('.') {
+            return Err(anyhow!("invalid plugin name {:?}", name));
+        }
+        let version = match version {
+            Some("") | Some("latest") | None => None,
+            Some(v) => {
+                version_req(v)?;
+                Some(v.to_string())
+            }
+        };
+        Ok(PluginSpec {
+            name: name.to_string(),
+            version,
+        })
+    }
+
+    pub fn version_req(&self) -> Result<VersionReq> {
+        match &self.version {
+            Some(v) => version_req(v),
+            None => Ok(VersionReq::STAR),
+        }
+    }
+}
+
+impl fmt::Display for PluginSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.version {
+            Some(v) => write!(f, "{}@{}", self.name, v),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+pub fn version_req(version: &str) -> Result<VersionReq> {
+    let version = version.trim_start_matches('v');
+    if let Ok(v) = Version::parse(version) {
+        return Ok(VersionReq::parse(&format!("={}", v))?);
+    }
+    VersionReq::parse(version).map_err(|e| anyhow!("invalid version {:?}: {}", version, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_spec() {
+        let spec = PluginSpec::parse("node").unwrap();
+        assert_eq!(spec.name, "node");
+        assert_eq!(spec.version, None);
+        let spec = PluginSpec::parse("node@20.1.0").unwrap();
+        assert_eq!(spec.version, Some("20.1.0".to_string()));
+        assert_eq!(spec.to_string(), "node@20.1.0");
+        assert!(PluginSpec::parse("@1.0.0").is_err());
+        assert!(PluginSpec::parse("../node").is_err());
+        assert!(PluginSpec::parse("node@not-a-version").is_err());
+    }
+
+    #[test]
+    fn test_version_req() {
+        let req = version_req("1.2.3").unwrap();
+        assert!(req.matches(&Version::parse("1.2.3").unwrap()));
+        assert!(!req.matches(&Version::parse("1.2.4").unwrap()));
+        let req = version_req("1.2").unwrap();
+        assert!(req.matches(&Version::parse("1.9.0").unwrap()));
+        assert!(!req.matches(&Version::parse("2.0.0").unwrap()));
+    }
+}
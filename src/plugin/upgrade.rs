@@ -0,0 +1,170 @@
+use anyhow::{anyhow, Result};
+use log::warn;
+use semver::{Version, VersionReq};
+use std::path::Path;
+
+use super::active::set_global;
+use super::install::{install, InstallOptions, InstallOutcome};
+use super::manifest::InstallManifest;
+use super::source::Source;
+use super::spec::PluginSpec;
+
+/// A newer version offered by the source than the highest matching one installed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Upgrade {
+    pub name: String,
+    pub from: Version,
+    pub to: Version,
+}
+
+// what `cot upgrade` would install, for one plugin or every installed one
+pub fn plan_upgrades(
+    home: &Path,
+    source: &Source,
+    spec: Option<&PluginSpec>,
+) -> Result<Vec<Upgrade>> {
+    let manifest = InstallManifest::load(home)?;
+    let targets = match spec {
+        Some(spec) => {
+            if manifest.versions(&spec.name).is_empty() {
+                return Err(anyhow!(
+                    "{} is not installed, run `cot install {}` first",
+                    spec.name,
+                    spec
+                ));
+            }
+            vec![(spec.name.clone(), spec.version_req()?)]
+        }
+        None => manifest
+            .plugins
+            .keys()
+            .map(|name| (name.clone(), VersionReq::STAR))
+            .collect(),
+    };
+
+    let mut upgrades = vec![];
+    for (name, req) in targets {
+        // the highest installed version of the line being upgraded, not of every line
+        let from = manifest
+            .versions(&name)
+            .iter()
+            .map(|v| &v.version)
+            .filter(|v| req.matches(v))
+            .max();
+        let Some(from) = from else {
+            if let Some(spec) = spec {
+                return Err(anyhow!(
+                    "no installed version of {} matches {}, run `cot install {}` first",
+                    name,
+                    req,
+                    spec
+                ));
+            }
+            continue;
+        };
+        let latest = match source.locate(&name, &req) {
+            Ok(artifact) => artifact.version,
+            // one plugin missing from the source shouldn't stop upgrading the others
+            Err(e) if spec.is_none() => {
+                warn!("skip {}: {}", name, e);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        if &latest > from {
+            upgrades.push(Upgrade {
+                name,
+                from: from.clone(),
+                to: latest,
+            });
+        }
+    }
+    Ok(upgrades)
+}
+
+// install the planned versions, and make them the global ones when switch is set
+pub fn upgrade(
+    home: &Path,
+    source: &Source,
+    upgrades: &[Upgrade],
+    options: &InstallOptions,
+    switch: bool,
+) -> Result<Vec<InstallOutcome>> {
+    let mut outcomes = vec![];
+    for upgrade in upgrades {
+        let spec = PluginSpec {
+            name: upgrade.name.clone(),
+            version: Some(upgrade.to.to_string()),
+        };
+        outcomes.push(install(home, source, &spec, options)?);
+        if switch {
+            set_global(home, &upgrade.name, &upgrade.to)?;
+        }
+    }
+    Ok(outcomes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::active::global_version;
+    use crate::plugin::install::tests::make_archive;
+
+    #[test]
+    fn test_upgrade() {
+        let home = tempfile::tempdir().unwrap();
+        let source_dir = tempfile::tempdir().unwrap();
+        for version in ["1.0.0", "1.1.0", "2.0.0"] {
+            make_archive(source_dir.path(), "hello", version);
+        }
+        let source = Source::parse(source_dir.path().to_str().unwrap());
+        let options = InstallOptions::default();
+        install(
+            home.path(),
+            &source,
+            &PluginSpec::parse("hello@1.0.0").unwrap(),
+            &options,
+        )
+        .unwrap();
+
+        let spec = PluginSpec::parse("hello@1").unwrap();
+        let upgrades = plan_upgrades(home.path(), &source, Some(&spec)).unwrap();
+        assert_eq!(
+            upgrades,
+            vec![Upgrade {
+                name: "hello".to_string(),
+                from: Version::parse("1.0.0").unwrap(),
+                to: Version::parse("1.1.0").unwrap(),
+            }]
+        );
+        upgrade(home.path(), &source, &upgrades, &options, true).unwrap();
+        assert_eq!(
+            global_version(home.path(), "hello"),
+            Some(Version::parse("1.1.0").unwrap())
+        );
+
+        let upgrades = plan_upgrades(home.path(), &source, None).unwrap();
+        assert_eq!(upgrades[0].to, Version::parse("2.0.0").unwrap());
+        upgrade(home.path(), &source, &upgrades, &options, false).unwrap();
+        assert!(plan_upgrades(home.path(), &source, None)
+            .unwrap()
+            .is_empty());
+        // without --use the global version stays
+        assert_eq!(
+            global_version(home.path(), "hello"),
+            Some(Version::parse("1.1.0").unwrap())
+        );
+
+        // with 1.x and 2.x installed, hello@1 upgrades the 1.x line
+        make_archive(source_dir.path(), "hello", "1.2.0");
+        let spec = PluginSpec::parse("hello@1").unwrap();
+        let upgrades = plan_upgrades(home.path(), &source, Some(&spec)).unwrap();
+        assert_eq!(upgrades[0].from, Version::parse("1.1.0").unwrap());
+        assert_eq!(upgrades[0].to, Version::parse("1.2.0").unwrap());
+        let spec = PluginSpec::parse("hello@3").unwrap();
+        assert!(plan_upgrades(home.path(), &source, Some(&spec)).is_err());
+
+        let missing = PluginSpec::parse("missing").unwrap();
+        assert!(plan_upgrades(home.path(), &source, Some(&missing)).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+use anyhow::{anyhow, Context, Result};
+use log::{debug, warn};
+use minisign_verify::{PublicKey, Signature};
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use super::install::utf8_path;
+use super::source::Artifact;
+use crate::util::sha256_file;
+
+pub const PUBLIC_KEY_FILE: &str = "minisign.pub";
+
+// COT_PUBLIC_KEY (a base64 key or a key file), otherwise $COT_HOME/minisign.pub if present
+pub fn public_key(home: &Path) -> Result<Option<PublicKey>> {
+    let key = match env::var("COT_PUBLIC_KEY") {
+        Ok(value) if !value.is_empty() => value,
+        _ => {
+            let path = home.join(PUBLIC_KEY_FILE);
+            if !path.is_file() {
+                return Ok(None);
+            }
+            path.to_string_lossy().to_string()
+        }
+    };
+    let public_key = if Path::new(&key).is_file() {
+        PublicKey::from_file(&key)
+    } else {
+        PublicKey::from_base64(key.trim())
+    };
+    public_key
+        .map(Some)
+        .map_err(|e| anyhow!("Failed to load minisign public key {}: {}", key, e))
+}
+
+pub fn check_checksum(file_name: &str, expected: &str, actual: &str) -> Result<()> {
+    if !expected.eq_ignore_ascii_case(actual) {
+        return Err(anyhow!(
+            "checksum mismatch for {}: expected sha256 {}, got {}",
+            file_name,
+            expected,
+            actual
+        ));
+    }
+    Ok(())
+}
+
+// signature is the content of a .minisig file
+pub fn verify_signature(archive: &Path, signature: &str, key: &PublicKey) -> Result<()> {
+    let signature = Signature::decode(signature)
+        .map_err(|e| anyhow!("Invalid signature for {}: {}", archive.display(), e))?;
+    let data =
+        fs::read(archive).with_context(|| format!("Failed to read {}", archive.display()))?;
+    key.verify(&data, &signature, false)
+        .map_err(|e| anyhow!("Signature check failed for {}: {}", archive.display(), e))
+}
+
+// checksum against the source, and the signature when a public key is configured, returns the sha256
+pub fn verify_artifact(
+    artifact: &Artifact,
+    archive: &Path,
+    key: Option<&PublicKey>,
+) -> Result<String> {
+    let sha256 = sha256_file(utf8_path(archive)?)?;
+    match &artifact.sha256 {
+        Some(expected) => check_checksum(&artifact.file_name(), expected, &sha256)?,
+        None => warn!(
+            "no checksum for {}, installing it unverified (sha256 {})",
+            artifact.file_name(),
+            sha256
+        ),
+    }
+    if let Some(key) = key {
+        let signature = artifact.signature.as_ref().ok_or_else(|| {
+            anyhow!(
+                "{}@{} is not signed, but a minisign public key is configured",
+                artifact.name,
+                artifact.version
+            )
+        })?;
+        verify_signature(archive, signature, key)?;
+        debug!("signature of {} verified", artifact.file_name());
+    }
+    Ok(sha256)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use semver::Version;
+
+    // from the minisign-verify test suite, a prehashed signature of "test"
+    const KEY: &str = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+    const SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=
+trusted comment: timestamp:1556193335\tfile:test
+y/rUw2y8/hOUYjZU71eHp/Wo1KZ40fGy2VJEDl34XMJM+TX48Ss/17u3IvIfbVR1FkZZSNCisQbuQY+bHwhEBg==";
+    const TEST_SHA256: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    fn artifact(path: &Path, sha256: Option<&str>, signature: Option<&str>) -> Artifact {
+        Artifact {
+            name: "hello".to_string(),
+            version: Version::parse("1.0.0").unwrap(),
+            url: path.to_string_lossy().to_string(),
+            sha256: sha256.map(|s| s.to_string()),
+            signature: signature.map(|s| s.to_string()),
+            depends: vec![],
+        }
+    }
+
+    #[test]
+    fn test_verify_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hello-1.0.0.tar.gz");
+        fs::write(&path, "test").unwrap();
+        let sha256 = verify_artifact(&artifact(&path, Some(TEST_SHA256), None), &path, None);
+        assert_eq!(sha256.unwrap(), TEST_SHA256);
+
+        let e = verify_artifact(&artifact(&path, Some("00"), None), &path, None)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            e,
+            format!(
+                "checksum mismatch for hello-1.0.0.tar.gz: expected sha256 00, got {}",
+                TEST_SHA256
+            )
+        );
+
+        // a path sha256_file can't take is an error, not the checksum of something else
+        #[cfg(unix)]
+        {
+            use std::ffi::OsStr;
+            use std::os::unix::ffi::OsStrExt;
+            let path = dir.path().join(OsStr::from_bytes(b"hello\xff.tar.gz"));
+            fs::write(&path, "test").unwrap();
+            let e = verify_artifact(&artifact(&path, None, None), &path, None).unwrap_err();
+            assert!(e.to_string().contains("not a valid UTF-8 path"));
+        }
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hello-1.0.0.tar.gz");
+        fs::write(&path, "test").unwrap();
+        let key = PublicKey::from_base64(KEY).unwrap();
+        verify_artifact(&artifact(&path, None, Some(SIGNATURE)), &path, Some(&key)).unwrap();
+        // unsigned artifacts are refused once a key is configured
+        assert!(verify_artifact(&artifact(&path, None, None), &path, Some(&key)).is_err());
+
+        fs::write(&path, "Test").unwrap();
+        let e = verify_artifact(&artifact(&path, None, Some(SIGNATURE)), &path, Some(&key));
+        assert!(e
+            .unwrap_err()
+            .to_string()
+            .contains("Signature check failed"));
+    }
+
+    #[test]
+    fn test_public_key() {
+        let home = tempfile::tempdir().unwrap();
+        if env::var("COT_PUBLIC_KEY").is_err() {
+            assert!(public_key(home.path()).unwrap().is_none());
+            fs::write(
+                home.path().join(PUBLIC_KEY_FILE),
+                format!("untrusted comment: minisign public key\n{}\n", KEY),
+            )
+            .unwrap();
+            assert!(public_key(home.path()).unwrap().is_some());
+        }
+    }
+}
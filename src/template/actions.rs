@@ -0,0 +1,240 @@
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::process::Command;
+
+use super::manifest::condition_holds;
+use super::render::render;
+use crate::util::{git_commit_all, git_init, git_tag_in};
+
+const DEFAULT_COMMIT_MESSAGE: &str = "Initial commit";
+const DEFAULT_TAG: &str = "0.1.0";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ActionKind {
+    GitInit,
+    GitCommit(String),
+    // a semver version, so `cot tag` can read it
+    GitTag(String),
+    // a shell command run in the new project
+    Run(String),
+}
+
+/// A step run in the new project once its files are written.
+///
+/// ```yaml
+/// actions:
+///   - git-init
+///   - run: cargo fmt
+///     when: rust
+///   - git-commit: Initial commit of {{name}}
+///   - git-tag: v0.1.0
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Value", into = "Value")]
+pub struct PostAction {
+    pub kind: ActionKind,
+    // same conditions as conditional files
+    pub when: Option<String>,
+}
+
+impl fmt::Display for ActionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActionKind::GitInit => write!(f, "git init"),
+            ActionKind::GitCommit(message) => write!(f, "git commit -m {:?}", message),
+            ActionKind::GitTag(tag) => write!(f, "git tag {}", tag),
+            ActionKind::Run(command) => write!(f, "{}", command),
+        }
+    }
+}
+
+impl ActionKind {
+    fn name(&self) -> &'static str {
+        match self {
+            ActionKind::GitInit => "git-init",
+            ActionKind::GitCommit(_) => "git-commit",
+            ActionKind::GitTag(_) => "git-tag",
+            ActionKind::Run(_) => "run",
+        }
+    }
+
+    fn argument(&self) -> Option<&str> {
+        match self {
+            ActionKind::GitInit => None,
+            ActionKind::GitCommit(arg) | ActionKind::GitTag(arg) | ActionKind::Run(arg) => {
+                Some(arg)
+            }
+        }
+    }
+
+    // git-commit and git-tag fall back to a default argument
+    fn parse(name: &str, argument: Option<String>) -> Result<ActionKind> {
+        match (name, argument) {
+            ("git-init", None) => Ok(ActionKind::GitInit),
+            ("git-commit", message) => Ok(ActionKind::GitCommit(
+                message.unwrap_or(DEFAULT_COMMIT_MESSAGE.to_string()),
+            )),
+            ("git-tag", tag) => Ok(ActionKind::GitTag(tag.unwrap_or(DEFAULT_TAG.to_string()))),
+            ("run", Some(command)) => Ok(ActionKind::Run(command)),
+            ("run", None) => Err(anyhow!("action run needs a command")),
+            (name, _) => Err(anyhow!(
+                "unknown action {}, use git-init, git-commit, git-tag or run",
+                name
+            )),
+        }
+    }
+}
+
+impl TryFrom<Value> for PostAction {
+    type Error = anyhow::Error;
+
+    // `- git-init`, or a map of one action to its argument plus an optional when
+    fn try_from(value: Value) -> Result<PostAction> {
+        let mapping = match value {
+            Value::String(name) => {
+                return Ok(PostAction {
+                    kind: ActionKind::parse(&name, None)?,
+                    when: None,
+                })
+            }
+            Value::Mapping(mapping) => mapping,
+            other => return Err(anyhow!("invalid action {:?}", other)),
+        };
+        let mut when = None;
+        let mut kind = None;
+        for (key, value) in mapping {
+            let key = key
+                .as_str()
+                .ok_or_else(|| anyhow!("invalid action key {:?}", key))?
+                .to_string();
+            let value = match value {
+                Value::Null => None,
+                Value::String(s) => Some(s),
+                Value::Number(n) => Some(n.to_string()),
+                other => return Err(anyhow!("invalid argument of {}: {:?}", key, other)),
+            };
+            if key == "when" {
+                when = value;
+            } else if kind.is_none() {
+                kind = Some(ActionKind::parse(&key, value)?);
+            } else {
+                return Err(anyhow!("more than one action in {}", key));
+            }
+        }
+        Ok(PostAction {
+            kind: kind.ok_or_else(|| anyhow!("action without a name"))?,
+            when,
+        })
+    }
+}
+
+impl From<PostAction> for Value {
+    fn from(action: PostAction) -> Value {
+        let name = action.kind.name();
+        if action.when.is_none() && action.kind.argument().is_none() {
+            return Value::String(name.to_string());
+        }
+        let mut mapping = Mapping::new();
+        let argument = match action.kind.argument() {
+            Some(argument) => Value::String(argument.to_string()),
+            None => Value::Null,
+        };
+        mapping.insert(Value::String(name.to_string()), argument);
+        if let Some(when) = action.when {
+            mapping.insert(Value::String("when".to_string()), Value::String(when));
+        }
+        Value::Mapping(mapping)
+    }
+}
+
+fn run_command(command: &str, dir: &Path) -> Result<()> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    let status = shell
+        .arg(command)
+        .current_dir(dir)
+        .status()
+        .with_context(|| format!("Failed to run {}", command))?;
+    if !status.success() {
+        return Err(anyhow!("{} failed with {}", command, status));
+    }
+    Ok(())
+}
+
+// run the actions whose condition holds in dir, returns what ran
+pub fn run_actions(
+    actions: &[PostAction],
+    dir: &Path,
+    vars: &BTreeMap<String, String>,
+) -> Result<Vec<ActionKind>> {
+    let dir_str = dir.to_string_lossy();
+    let mut ran = vec![];
+    for action in actions {
+        if let Some(when) = &action.when {
+            if !condition_holds(when, vars)? {
+                continue;
+            }
+        }
+        let kind = match &action.kind {
+            ActionKind::GitInit => ActionKind::GitInit,
+            ActionKind::GitCommit(message) => ActionKind::GitCommit(render(message, vars)),
+            ActionKind::GitTag(tag) => ActionKind::GitTag(render(tag, vars)),
+            ActionKind::Run(command) => ActionKind::Run(render(command, vars)),
+        };
+        debug!("{} in {}", kind, dir.display());
+        match &kind {
+            ActionKind::GitInit => git_init(&dir_str)?,
+            ActionKind::GitCommit(message) => git_commit_all(&dir_str, message)?,
+            ActionKind::GitTag(tag) => {
+                Version::parse(tag.strip_prefix('v').unwrap_or(tag)).with_context(|| {
+                    format!("git-tag {} is not a semver version `cot tag` can read", tag)
+                })?;
+                git_tag_in(&dir_str, tag)?;
+            }
+            ActionKind::Run(command) => run_command(command, dir)?,
+        }
+        ran.push(kind);
+    }
+    Ok(ran)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_actions() {
+        let actions: Vec<PostAction> = serde_yaml::from_str(
+            "- git-init\n- git-commit\n- git-tag: v1.0.0\n- run: cargo fmt\n  when: rust\n",
+        )
+        .unwrap();
+        assert_eq!(actions[0].kind, ActionKind::GitInit);
+        assert_eq!(
+            actions[1].kind,
+            ActionKind::GitCommit("Initial commit".to_string())
+        );
+        assert_eq!(actions[2].kind, ActionKind::GitTag("v1.0.0".to_string()));
+        assert_eq!(actions[3].when.as_deref(), Some("rust"));
+        let yaml = serde_yaml::to_string(&actions).unwrap();
+        assert_eq!(
+            serde_yaml::from_str::<Vec<PostAction>>(&yaml).unwrap(),
+            actions
+        );
+
+        assert!(serde_yaml::from_str::<Vec<PostAction>>("- git-push\n").is_err());
+        assert!(serde_yaml::from_str::<Vec<PostAction>>("- run\n").is_err());
+    }
+}
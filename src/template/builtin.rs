@@ -0,0 +1,75 @@
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+
+use super::manifest::{TemplateManifest, MANIFEST_FILE};
+use crate::Asset;
+
+// embedded under static/templates/<name>/
+const BUILTIN_PREFIX: &str = "templates/";
+
+/// A template shipped inside cot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuiltinTemplate {
+    pub name: String,
+    pub manifest: TemplateManifest,
+}
+
+pub fn builtin_templates() -> Result<Vec<BuiltinTemplate>> {
+    let mut templates = vec![];
+    for file in Asset::iter() {
+        let Some(rest) = file.strip_prefix(BUILTIN_PREFIX) else {
+            continue;
+        };
+        let Some((name, MANIFEST_FILE)) = rest.split_once('/') else {
+            continue;
+        };
+        let data = Asset::get(&file).map(|f| f.data).unwrap_or_default();
+        templates.push(BuiltinTemplate {
+            name: name.to_string(),
+            manifest: TemplateManifest::parse(&String::from_utf8_lossy(&data))?,
+        });
+    }
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(templates)
+}
+
+// write the files of builtin template name into dest, false when there is no such template
+pub fn write_builtin(name: &str, dest: &Path) -> Result<bool> {
+    let prefix = format!("{}{}/", BUILTIN_PREFIX, name);
+    let mut found = false;
+    for file in Asset::iter() {
+        let Some(relative) = file.strip_prefix(&prefix) else {
+            continue;
+        };
+        let target = dest.join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let data = Asset::get(&file).map(|f| f.data).unwrap_or_default();
+        fs::write(target, data)?;
+        found = true;
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_templates() {
+        let names: Vec<String> = builtin_templates()
+            .unwrap()
+            .into_iter()
+            .map(|t| t.name)
+            .collect();
+        assert_eq!(names, vec!["hf-finetune", "python", "rust-bin", "rust-lib"]);
+
+        let dir = tempfile::tempdir().unwrap();
+        assert!(write_builtin("rust-bin", dir.path()).unwrap());
+        assert!(dir.path().join("Cargo.toml.tmpl").is_file());
+        assert!(dir.path().join(".gitignore").is_file());
+        assert!(!write_builtin("cobol", dir.path()).unwrap());
+    }
+}
@@ -0,0 +1,658 @@
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Component, Path, PathBuf};
+use tempfile::TempDir;
+
+use super::actions::{run_actions, ActionKind};
+use super::builtin::write_builtin;
+use super::manifest::{TemplateManifest, TemplateVariable, MANIFEST_FILE};
+use super::render::{placeholders, render};
+use super::repo::{checkout_template_repo, is_template_repo, split_template_dir};
+use crate::plugin::{archive_extension, content_root, unpack};
+use crate::util::{git_archive, git_head_of, git_toplevel};
+
+// what the project remembers of its template, for `cot init --update`
+pub const RECORD_FILE: &str = ".cot-init.yaml";
+
+// answers not matching a variable's regex before giving up
+const MAX_PROMPTS: usize = 3;
+
+/// A template directory, written to a temp dir when it came as an archive or is built in.
+#[derive(Debug)]
+pub struct TemplateDir {
+    pub root: PathBuf,
+    // set when the template comes out of a git repository, older revisions can be had
+    pub git: Option<TemplateGit>,
+    // keeps the unpacked archive around as long as the template is used
+    _unpacked: Option<TempDir>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateGit {
+    pub repo: PathBuf,
+    // the template's directory in the repo
+    pub dir: PathBuf,
+    pub revision: String,
+}
+
+impl TemplateGit {
+    // none when root isn't in repo or repo has no commit yet
+    fn of(repo: &Path, root: &Path) -> Option<TemplateGit> {
+        let repo = fs::canonicalize(repo).ok()?;
+        let dir = fs::canonicalize(root)
+            .ok()?
+            .strip_prefix(&repo)
+            .ok()?
+            .to_path_buf();
+        let revision = git_head_of(&repo.to_string_lossy()).ok()?;
+        Some(TemplateGit {
+            repo,
+            dir,
+            revision,
+        })
+    }
+}
+
+impl TemplateDir {
+    pub fn open(template: &str, cache: Option<&Path>) -> Result<TemplateDir> {
+        let (location, dir) = split_template_dir(template);
+        if is_template_repo(location) {
+            let cache =
+                cache.ok_or_else(|| anyhow!("no template cache to clone {} into", location))?;
+            let checkout = checkout_template_repo(location, cache)?;
+            return TemplateDir::within(checkout.clone(), dir, None, Some(&checkout));
+        }
+        let path = PathBuf::from(location);
+        if path.is_dir() {
+            // a template kept in a work tree has the history of its repo
+            let repo = git_toplevel(location).map(PathBuf::from);
+            return TemplateDir::within(path, dir, None, repo.as_deref());
+        }
+        if !path.is_file() {
+            let unpacked = tempfile::tempdir()?;
+            if !write_builtin(location, unpacked.path())? {
+                return Err(anyhow!(
+                    "template {} not found, `cot init --list` shows the built-in ones",
+                    template
+                ));
+            }
+            let root = unpacked.path().to_path_buf();
+            return TemplateDir::within(root, dir, Some(unpacked), None);
+        }
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        if archive_extension(&file_name).is_none() {
+            return Err(anyhow!(
+                "template {} is neither a directory, git repository nor a .tar.gz, .tgz, .tar or .zip archive",
+                template
+            ));
+        }
+        let unpacked = tempfile::tempdir()?;
+        unpack(&path, unpacked.path())
+            .with_context(|| format!("Failed to unpack template {}", template))?;
+        let root = content_root(unpacked.path())?;
+        TemplateDir::within(root, dir, Some(unpacked), None)
+    }
+
+    // the template in dir of root, root itself without a dir
+    fn within(
+        root: PathBuf,
+        dir: Option<&str>,
+        unpacked: Option<TempDir>,
+        repo: Option<&Path>,
+    ) -> Result<TemplateDir> {
+        let root = match dir {
+            Some(dir) => root.join(dir),
+            None => root,
+        };
+        if !root.is_dir() {
+            return Err(anyhow!("no template directory {}", root.display()));
+        }
+        Ok(TemplateDir {
+            git: repo.and_then(|repo| TemplateGit::of(repo, &root)),
+            root,
+            _unpacked: unpacked,
+        })
+    }
+
+    // the same template as it was at an earlier revision of its repo
+    pub fn at_revision(&self, revision: &str) -> Result<TemplateDir> {
+        let git = self
+            .git
+            .as_ref()
+            .ok_or_else(|| anyhow!("template {} has no git history", self.root.display()))?;
+        let unpacked = tempfile::tempdir()?;
+        git_archive(
+            &git.repo.to_string_lossy(),
+            revision,
+            &unpacked.path().to_string_lossy(),
+        )
+        .with_context(|| format!("Failed to get revision {} of the template", revision))?;
+        let root = unpacked.path().join(&git.dir);
+        let dir = TemplateDir::within(root, None, Some(unpacked), None)?;
+        Ok(dir)
+    }
+
+    // files of the template relative to its root, .git and the manifest left out
+    pub fn files(&self) -> Result<Vec<PathBuf>> {
+        let mut files = vec![];
+        collect_files(&self.root, Path::new(""), &mut files)?;
+        files.retain(|file| file != Path::new(MANIFEST_FILE));
+        files.sort();
+        Ok(files)
+    }
+}
+
+fn collect_files(root: &Path, relative: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(root.join(relative))? {
+        let entry = entry?;
+        if entry.file_name() == ".git" {
+            continue;
+        }
+        let path = relative.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            collect_files(root, &path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct InitOptions {
+    // --set key=value, ahead of the environment
+    pub vars: BTreeMap<String, String>,
+    // ask on the terminal for vars found nowhere else
+    pub interactive: bool,
+    // where template repos are cloned, none to refuse them
+    pub cache: Option<PathBuf>,
+    // run the template's actions in the new project
+    pub run_actions: bool,
+}
+
+/// What `cot init` created.
+#[derive(Debug, Default)]
+pub struct Scaffold {
+    pub files: Vec<PathBuf>,
+    pub actions: Vec<ActionKind>,
+}
+
+// key=value of --set
+pub fn parse_var(set: &str) -> Result<(String, String)> {
+    match set.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.to_string()))
+        }
+        _ => Err(anyhow!("invalid --set {:?}, use key=value", set)),
+    }
+}
+
+// placeholders used in the paths and text files of the template
+fn template_vars(template: &TemplateDir, files: &[PathBuf]) -> Result<BTreeSet<String>> {
+    let mut names = BTreeSet::new();
+    for file in files {
+        names.extend(placeholders(&file.to_string_lossy()));
+        if let Ok(text) = fs::read_to_string(template.root.join(file)) {
+            names.extend(placeholders(&text));
+        }
+    }
+    Ok(names)
+}
+
+fn from_env(name: &str) -> Option<String> {
+    env::var(name)
+        .or_else(|_| env::var(name.to_uppercase()))
+        .ok()
+}
+
+// the manifest's variables in order: --set, $name or $NAME, a prompt, then the default
+fn resolve_declared(
+    manifest: &TemplateManifest,
+    project: &str,
+    options: &InitOptions,
+) -> Result<BTreeMap<String, String>> {
+    let mut vars = BTreeMap::from([
+        ("name".to_string(), project.to_string()),
+        ("name_snake".to_string(), project.replace('-', "_")),
+    ]);
+    vars.extend(options.vars.clone());
+    for variable in &manifest.variables {
+        let default = variable.default.as_ref().map(|d| render(d, &vars));
+        let value = match vars
+            .get(&variable.name)
+            .cloned()
+            .or(from_env(&variable.name))
+        {
+            Some(value) => value,
+            None if options.interactive => {
+                ask(variable, default.as_deref(), &mut io::stdin().lock())?
+            }
+            None => default.ok_or_else(|| missing_var(&variable.name))?,
+        };
+        variable.check(&value)?;
+        vars.insert(variable.name.clone(), value);
+    }
+    Ok(vars)
+}
+
+// placeholders the manifest doesn't declare: --set, $name or $NAME, then a prompt
+fn resolve_placeholders(
+    names: &BTreeSet<String>,
+    vars: &mut BTreeMap<String, String>,
+    options: &InitOptions,
+) -> Result<()> {
+    for name in names {
+        if vars.contains_key(name) {
+            continue;
+        }
+        let value = match from_env(name) {
+            Some(value) => value,
+            None if options.interactive => prompt(name, None, &mut io::stdin().lock())?,
+            None => return Err(missing_var(name)),
+        };
+        vars.insert(name.clone(), value);
+    }
+    Ok(())
+}
+
+fn missing_var(name: &str) -> anyhow::Error {
+    anyhow!("no value for {{{{{}}}}}, pass --set {}=<value>", name, name)
+}
+
+// the answer, or default on an empty one
+// asked again while the answer doesn't match the variable's regex, up to MAX_PROMPTS times
+fn ask(
+    variable: &TemplateVariable,
+    default: Option<&str>,
+    input: &mut dyn BufRead,
+) -> Result<String> {
+    let label = variable.prompt.as_deref().unwrap_or(&variable.name);
+    let mut attempts = 0;
+    loop {
+        let value = prompt(label, default, input)?;
+        match variable.check(&value) {
+            Ok(()) => return Ok(value),
+            Err(e) if attempts + 1 >= MAX_PROMPTS => return Err(e),
+            Err(e) => eprintln!("{}", e),
+        }
+        attempts += 1;
+    }
+}
+
+fn prompt(label: &str, default: Option<&str>, input: &mut dyn BufRead) -> Result<String> {
+    let mut stderr = io::stderr();
+    match default {
+        Some(default) => write!(stderr, "{} [{}]: ", label, default)?,
+        None => write!(stderr, "{}: ", label)?,
+    }
+    stderr.flush()?;
+    let mut line = String::new();
+    // ctrl-d, or stdin closed
+    if input.read_line(&mut line)? == 0 {
+        return Err(anyhow!("no answer for {}, input closed", label));
+    }
+    let answer = line.trim_end_matches(['\r', '\n']);
+    match default {
+        Some(default) if answer.is_empty() => Ok(default.to_string()),
+        _ => Ok(answer.to_string()),
+    }
+}
+
+pub fn is_interactive() -> bool {
+    io::stdin().is_terminal()
+}
+
+// where file of the template goes, a .tmpl suffix is dropped so Cargo.toml.tmpl gives Cargo.toml
+// a value such as ../../x in a path placeholder must not write outside the project
+fn target_path(file: &Path, vars: &BTreeMap<String, String>) -> Result<PathBuf> {
+    let rendered = render(&file.to_string_lossy(), vars);
+    let target = PathBuf::from(rendered.strip_suffix(".tmpl").unwrap_or(&rendered));
+    let inside = target
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if !inside || target.as_os_str().is_empty() {
+        return Err(anyhow!(
+            "{} renders to {}, which is outside the project",
+            file.display(),
+            target.display()
+        ));
+    }
+    Ok(target)
+}
+
+/// The template and variables a project was created with, kept in its `.cot-init.yaml`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InitRecord {
+    pub template: String,
+    // commit of the template's repo the project is at, none without a repo
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+    #[serde(default)]
+    pub vars: BTreeMap<String, String>,
+}
+
+impl InitRecord {
+    pub fn load(project: &Path) -> Result<InitRecord> {
+        let path = project.join(RECORD_FILE);
+        let data = fs::read_to_string(&path).with_context(|| {
+            format!(
+                "Failed to read {}, was the project created by `cot init`?",
+                path.display()
+            )
+        })?;
+        serde_yaml::from_str(&data).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn save(&self, project: &Path) -> Result<()> {
+        let path = project.join(RECORD_FILE);
+        fs::write(&path, serde_yaml::to_string(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+// local templates are recorded by absolute path, so --update works from anywhere
+fn record_location(template: &str) -> Result<String> {
+    let (location, dir) = split_template_dir(template);
+    if !Path::new(location).exists() {
+        return Ok(template.to_string());
+    }
+    let location = std::path::absolute(location)?.to_string_lossy().to_string();
+    Ok(match dir {
+        Some(dir) => format!("{}#{}", location, dir),
+        None => location,
+    })
+}
+
+// the files of template rendered into dest, and the vars they were rendered with
+pub fn render_template(
+    template: &TemplateDir,
+    project: &str,
+    dest: &Path,
+    options: &InitOptions,
+) -> Result<(Vec<PathBuf>, BTreeMap<String, String>)> {
+    let manifest = TemplateManifest::load(&template.root)?;
+    let mut vars = resolve_declared(&manifest, project, options)?;
+    let mut files = vec![];
+    for file in template.files()? {
+        if !manifest.excluded(&file, &vars)? {
+            files.push(file);
+        }
+    }
+    resolve_placeholders(&template_vars(template, &files)?, &mut vars, options)?;
+
+    // every target is checked before anything is written
+    let targets = files
+        .iter()
+        .map(|file| target_path(file, &vars))
+        .collect::<Result<Vec<_>>>()?;
+    let mut written = vec![];
+    for (file, relative) in files.iter().zip(targets) {
+        let source = template.root.join(file);
+        let target = dest.join(&relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        debug!("{} -> {}", source.display(), target.display());
+        match fs::read_to_string(&source) {
+            Ok(text) => {
+                fs::write(&target, render(&text, &vars))?;
+                fs::set_permissions(&target, source.metadata()?.permissions())?;
+            }
+            // binary files are copied as they are
+            Err(_) => {
+                fs::copy(&source, &target)?;
+            }
+        }
+        written.push(relative);
+    }
+    Ok((written, vars))
+}
+
+// `cot init <template> <dest>`: the template with its placeholders filled, then its actions run
+pub fn init_project(template: &str, dest: &Path, options: &InitOptions) -> Result<Scaffold> {
+    if dest.exists() && fs::read_dir(dest)?.next().is_some() {
+        return Err(anyhow!(
+            "{} already exists and is not empty",
+            dest.display()
+        ));
+    }
+    let project = dest
+        .file_name()
+        .ok_or_else(|| anyhow!("no project name in {}", dest.display()))?
+        .to_string_lossy()
+        .to_string();
+    let location = record_location(template)?;
+    let template = TemplateDir::open(template, options.cache.as_deref())?;
+    let (files, vars) = render_template(&template, &project, dest, options)?;
+    // before the actions, so an initial commit has it
+    let record = InitRecord {
+        template: location,
+        revision: template.git.as_ref().map(|git| git.revision.clone()),
+        vars: vars.clone(),
+    };
+    record.save(dest)?;
+
+    let actions = match options.run_actions {
+        true => {
+            let manifest = TemplateManifest::load(&template.root)?;
+            run_actions(&manifest.actions, dest, &vars)?
+        }
+        false => vec![],
+    };
+    Ok(Scaffold {
+        files: files.iter().map(|file| dest.join(file)).collect(),
+        actions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_template(dir: &Path) {
+        fs::create_dir_all(dir.join("src").join("{{name}}")).unwrap();
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::write(dir.join(".git").join("HEAD"), "ref: {{branch}}").unwrap();
+        fs::write(
+            dir.join("README.md"),
+            "# {{name}}\nby {{author}} {{ license }}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("src").join("{{name}}").join("main.txt"),
+            "{{name}}",
+        )
+        .unwrap();
+        fs::write(dir.join("logo.bin"), [0xff, 0xfe, 0x7b, 0x7b]).unwrap();
+        fs::write(dir.join("Cargo.toml.tmpl"), "name = \"{{name_snake}}\"").unwrap();
+        fs::write(dir.join(MANIFEST_FILE), "description: test").unwrap();
+    }
+
+    #[test]
+    fn test_init_project() {
+        let template = tempfile::tempdir().unwrap();
+        make_template(template.path());
+        let out = tempfile::tempdir().unwrap();
+        let dest = out.path().join("demo");
+        let template = template.path().to_str().unwrap();
+
+        let license = parse_var("license=MIT").unwrap();
+        let options = InitOptions {
+            vars: BTreeMap::from([license.clone()]),
+            ..InitOptions::default()
+        };
+        let err = init_project(template, &dest, &options).unwrap_err();
+        assert!(err.to_string().contains("--set author=<value>"), "{}", err);
+
+        let options = InitOptions {
+            vars: BTreeMap::from([license, parse_var("author=Ann").unwrap()]),
+            ..InitOptions::default()
+        };
+        let written = init_project(template, &dest, &options).unwrap().files;
+        assert_eq!(written.len(), 4);
+        assert_eq!(
+            fs::read_to_string(dest.join("Cargo.toml")).unwrap(),
+            "name = \"demo\""
+        );
+        assert!(!dest.join(MANIFEST_FILE).exists());
+        assert_eq!(
+            fs::read_to_string(dest.join("README.md")).unwrap(),
+            "# demo\nby Ann MIT\n"
+        );
+        let main = dest.join("src").join("demo").join("main.txt");
+        assert_eq!(fs::read_to_string(main).unwrap(), "demo");
+        assert_eq!(
+            fs::read(dest.join("logo.bin")).unwrap(),
+            [0xff, 0xfe, 0x7b, 0x7b]
+        );
+        assert!(!dest.join(".git").exists());
+        assert!(init_project(template, &dest, &options).is_err());
+        assert!(parse_var("=x").is_err());
+        // $name, then $NAME
+        assert_eq!(from_env("path"), env::var("PATH").ok());
+    }
+
+    #[test]
+    fn test_ask() {
+        let variable = TemplateVariable {
+            name: "port".to_string(),
+            regex: Some("^[0-9]+$".to_string()),
+            ..TemplateVariable::default()
+        };
+        let mut input = io::Cursor::new("http\n8080\n");
+        assert_eq!(ask(&variable, None, &mut input).unwrap(), "8080");
+        let mut input = io::Cursor::new("");
+        let err = ask(&variable, None, &mut input).unwrap_err();
+        assert!(err.to_string().contains("input closed"), "{}", err);
+        let mut input = io::Cursor::new("a\nb\nc\nd\n");
+        assert!(ask(&variable, None, &mut input).is_err());
+        assert_eq!(input.position(), 6);
+    }
+
+    #[test]
+    fn test_init_outside_project() {
+        let template = tempfile::tempdir().unwrap();
+        fs::create_dir(template.path().join("{{dir}}")).unwrap();
+        fs::write(template.path().join("{{dir}}").join("x.txt"), "x").unwrap();
+        let out = tempfile::tempdir().unwrap();
+        let dest = out.path().join("demo");
+        let template = template.path().to_str().unwrap();
+        for dir in ["../../escape", "/tmp/escape", ".."] {
+            let options = InitOptions {
+                vars: BTreeMap::from([("dir".to_string(), dir.to_string())]),
+                ..InitOptions::default()
+            };
+            let err = init_project(template, &dest, &options).unwrap_err();
+            assert!(err.to_string().contains("outside the project"), "{}", err);
+        }
+        assert!(!out.path().join("escape").exists());
+        assert!(!dest.exists());
+    }
+
+    #[test]
+    fn test_init_from_archive() {
+        let template = tempfile::tempdir().unwrap();
+        let archive = template.path().join("starter.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&archive).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file("starter-main/hello.txt", options).unwrap();
+        zip.write_all(b"hello {{name}}").unwrap();
+        zip.finish().unwrap();
+
+        let out = tempfile::tempdir().unwrap();
+        let dest = out.path().join("app");
+        let options = InitOptions::default();
+        init_project(archive.to_str().unwrap(), &dest, &options).unwrap();
+        assert_eq!(
+            fs::read_to_string(dest.join("hello.txt")).unwrap(),
+            "hello app"
+        );
+    }
+
+    #[test]
+    fn test_init_builtin() {
+        let out = tempfile::tempdir().unwrap();
+        let dest = out.path().join("my-pkg");
+        let written = init_project("python", &dest, &InitOptions::default())
+            .unwrap()
+            .files;
+        assert!(written.contains(&dest.join("src").join("my_pkg").join("__init__.py")));
+        let test = fs::read_to_string(dest.join("tests").join("test_my_pkg.py")).unwrap();
+        assert!(test.contains("from my_pkg import hello"), "{}", test);
+        assert!(init_project("cobol", &out.path().join("x"), &InitOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_init_from_repo() {
+        use crate::template::repo::tests::make_template_repo;
+
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = "variables:\n  - name: port\n    default: \"80{{name_snake}}\"\n  - name: owner\n    regex: ^[a-z]+$\n  - name: docker\n    default: \"no\"\nfiles:\n  - when: docker\n    paths: [Dockerfile]\n";
+        let bare = make_template_repo(
+            dir.path(),
+            &[
+                ("svc/cot-template.yaml", manifest),
+                ("svc/main.txt", "{{name}} on {{port}} by {{owner}}"),
+                ("svc/Dockerfile", "EXPOSE {{port}} {{registry}}"),
+            ],
+        );
+        let template = format!("{}#svc", bare.display());
+        let cache = tempfile::tempdir().unwrap();
+        let out = tempfile::tempdir().unwrap();
+        let dest = out.path().join("42");
+
+        let mut options = InitOptions {
+            vars: BTreeMap::from([parse_var("owner=ann").unwrap()]),
+            ..InitOptions::default()
+        };
+        let err = init_project(&template, &dest, &options).unwrap_err();
+        assert!(err.to_string().contains("template cache"), "{}", err);
+
+        options.cache = Some(cache.path().to_path_buf());
+        options.vars.insert("owner".to_string(), "Ann".to_string());
+        let err = init_project(&template, &dest, &options).unwrap_err();
+        assert!(err.to_string().contains("does not match"), "{}", err);
+
+        options.vars.insert("owner".to_string(), "ann".to_string());
+        let written = init_project(&template, &dest, &options).unwrap().files;
+        assert_eq!(written, vec![dest.join("main.txt")]);
+        assert_eq!(
+            fs::read_to_string(dest.join("main.txt")).unwrap(),
+            "42 on 8042 by ann"
+        );
+    }
+
+    #[test]
+    fn test_init_actions() {
+        use crate::util::git_run;
+
+        let template = tempfile::tempdir().unwrap();
+        fs::write(template.path().join("README.md"), "# {{name}}\n").unwrap();
+        // no git identity to rely on where the tests run, the repo gets its own
+        fs::write(
+            template.path().join(MANIFEST_FILE),
+            "actions:\n  - git-init\n  - run: git config user.name cot && git config user.email cot@localhost\n  - git-commit\n  - git-tag: 0.1.0\n",
+        )
+        .unwrap();
+        let out = tempfile::tempdir().unwrap();
+        let dest = out.path().join("tool");
+        let options = InitOptions {
+            run_actions: true,
+            ..InitOptions::default()
+        };
+        let template = template.path().to_str().unwrap();
+        let scaffold = init_project(template, &dest, &options).unwrap();
+        assert_eq!(scaffold.actions.len(), 4);
+        let dest = dest.to_string_lossy();
+        assert_eq!(git_run(&["-C", &dest, "tag"]).unwrap(), "0.1.0");
+        assert_eq!(
+            git_run(&["-C", &dest, "status", "--porcelain"]).unwrap(),
+            ""
+        );
+    }
+}
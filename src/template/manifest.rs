@@ -0,0 +1,178 @@
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use super::actions::PostAction;
+
+// describes a template, never copied into the project
+pub const MANIFEST_FILE: &str = "cot-template.yaml";
+
+/// The `cot-template.yaml` at the root of a template.
+///
+/// ```yaml
+/// description: Rust web service
+/// variables:
+///   - name: port
+///     prompt: Port to listen on
+///     default: "8080"
+///     regex: ^[0-9]+$
+///   - name: docker
+///     default: "no"
+/// files:
+///   - when: docker
+///     paths: [Dockerfile, deploy/]
+/// actions:
+///   - git-init
+///   - git-commit
+///   - git-tag: 0.1.0
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TemplateManifest {
+    #[serde(default)]
+    pub description: String,
+    // asked for in this order, before any other placeholder
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variables: Vec<TemplateVariable>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<ConditionalFiles>,
+    // run in the new project after its files are written
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<PostAction>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TemplateVariable {
+    pub name: String,
+    // shown when asking, the name otherwise
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    // may use the variables before it, such as "{{name}}-service"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    // the value must match, anchor it with ^ and $ to match all of it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+}
+
+/// Template files only created when a condition on the variables holds.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConditionalFiles {
+    // `var`, `!var`, `var == value` or `var != value`
+    pub when: String,
+    // files, or directories with everything below them, as named in the template
+    pub paths: Vec<String>,
+}
+
+impl TemplateManifest {
+    pub fn parse(data: &str) -> Result<TemplateManifest> {
+        serde_yaml::from_str(data).with_context(|| format!("Failed to parse {}", MANIFEST_FILE))
+    }
+
+    // the template's manifest, an empty one when it has none
+    pub fn load(root: &Path) -> Result<TemplateManifest> {
+        let path = root.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(TemplateManifest::default());
+        }
+        let data = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        TemplateManifest::parse(&data)
+    }
+
+    // whether file of the template is left out with these vars
+    pub fn excluded(&self, file: &Path, vars: &BTreeMap<String, String>) -> Result<bool> {
+        for rule in &self.files {
+            if !rule.paths.iter().any(|path| file.starts_with(path)) {
+                continue;
+            }
+            if !condition_holds(&rule.when, vars)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl TemplateVariable {
+    pub fn check(&self, value: &str) -> Result<()> {
+        let Some(regex) = &self.regex else {
+            return Ok(());
+        };
+        let compiled = Regex::new(regex)
+            .with_context(|| format!("Invalid regex {:?} of variable {}", regex, self.name))?;
+        if !compiled.is_match(value) {
+            return Err(anyhow!(
+                "{} {:?} does not match {}",
+                self.name,
+                value,
+                regex
+            ));
+        }
+        Ok(())
+    }
+}
+
+// "", false, no, n, off and 0 are false, any other value true
+fn truthy(value: &str) -> bool {
+    !matches!(
+        value.trim().to_lowercase().as_str(),
+        "" | "false" | "no" | "n" | "off" | "0"
+    )
+}
+
+// `var`, `!var`, `var == value` or `var != value`, a missing var is empty
+pub fn condition_holds(when: &str, vars: &BTreeMap<String, String>) -> Result<bool> {
+    let value_of = |name: &str| vars.get(name.trim()).map(String::as_str).unwrap_or("");
+    let unquote = |value: &str| value.trim().trim_matches(['"', '\'']).to_string();
+    if let Some((name, value)) = when.split_once("!=") {
+        return Ok(value_of(name) != unquote(value));
+    }
+    if let Some((name, value)) = when.split_once("==") {
+        return Ok(value_of(name) == unquote(value));
+    }
+    let when = when.trim();
+    if when.is_empty() || when.contains(char::is_whitespace) {
+        return Err(anyhow!(
+            "invalid condition {:?}, use var, !var, var == value or var != value",
+            when
+        ));
+    }
+    match when.strip_prefix('!') {
+        Some(name) => Ok(!truthy(value_of(name))),
+        None => Ok(truthy(value_of(when))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest() {
+        let manifest = TemplateManifest::parse(
+            "description: svc\nvariables:\n  - name: port\n    default: \"8080\"\n    regex: ^[0-9]+$\nfiles:\n  - when: docker\n    paths: [Dockerfile, deploy/]\n  - when: ci == 'github'\n    paths: [.github]\n",
+        )
+        .unwrap();
+        assert_eq!(manifest.variables[0].default.as_deref(), Some("8080"));
+        assert!(manifest.variables[0].check("80").is_ok());
+        assert!(manifest.variables[0].check("eighty").is_err());
+
+        let vars = BTreeMap::from([
+            ("docker".to_string(), "no".to_string()),
+            ("ci".to_string(), "github".to_string()),
+        ]);
+        let excluded = |file: &str| manifest.excluded(Path::new(file), &vars).unwrap();
+        assert!(excluded("Dockerfile"));
+        assert!(excluded("deploy/k8s.yaml"));
+        assert!(!excluded("deployment.md"));
+        assert!(!excluded(".github/workflows/ci.yml"));
+
+        assert!(condition_holds("!docker", &vars).unwrap());
+        assert!(condition_holds("ci != gitlab", &vars).unwrap());
+        assert!(!condition_holds("missing", &vars).unwrap());
+        assert!(condition_holds("docker and ci", &vars).is_err());
+    }
+}
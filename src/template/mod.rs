@@ -0,0 +1,12 @@
+mod actions;
+mod builtin;
+mod init;
+mod manifest;
+mod render;
+mod repo;
+mod update;
+
+pub use self::builtin::*;
+pub use self::init::*;
+pub use self::repo::*;
+pub use self::update::*;
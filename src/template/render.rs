@@ -0,0 +1,46 @@
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet};
+
+// {{name}} or {{ name }}, anything else between braces is left alone
+fn placeholder_regex() -> Regex {
+    Regex::new(r"\{\{\s*([A-Za-z_][A-Za-z0-9_]*)\s*\}\}").unwrap()
+}
+
+// names of the placeholders in text
+pub fn placeholders(text: &str) -> BTreeSet<String> {
+    placeholder_regex()
+        .captures_iter(text)
+        .map(|caps| caps[1].to_string())
+        .collect()
+}
+
+// fill the placeholders of text from vars, unknown ones are kept as they are
+pub fn render(text: &str, vars: &BTreeMap<String, String>) -> String {
+    placeholder_regex()
+        .replace_all(text, |caps: &regex::Captures| match vars.get(&caps[1]) {
+            Some(value) => value.clone(),
+            None => caps[0].to_string(),
+        })
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let text = "# {{name}}\nby {{ author }}, ${{cost}} {{ .Values.x }} {{missing}}";
+        let names: Vec<String> = placeholders(text).into_iter().collect();
+        assert_eq!(names, vec!["author", "cost", "missing", "name"]);
+        let vars = BTreeMap::from([
+            ("name".to_string(), "demo".to_string()),
+            ("author".to_string(), "Ann".to_string()),
+            ("cost".to_string(), "5".to_string()),
+        ]);
+        assert_eq!(
+            render(text, &vars),
+            "# demo\nby Ann, $5 {{ .Values.x }} {{missing}}"
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use anyhow::{anyhow, Context, Result};
+use log::{debug, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::util::{
+    git_clone, git_dir_is_repo, git_installed, git_is_bare, git_sync, sha256_str, xdg_cache_home,
+};
+
+// $XDG_CACHE_HOME/cot/templates, or $COT_HOME/cache/templates without a cache home
+pub fn template_cache(home: &Path) -> PathBuf {
+    match xdg_cache_home() {
+        Ok(cache) => PathBuf::from(cache).join("cot").join("templates"),
+        Err(_) => home.join("cache").join("templates"),
+    }
+}
+
+// https://, ssh://, git://, file:// and git@host:path urls, or anything ending in .git
+pub fn is_git_url(location: &str) -> bool {
+    ["https://", "http://", "ssh://", "git://", "file://", "git@"]
+        .iter()
+        .any(|scheme| location.starts_with(scheme))
+        || location.trim_end_matches('/').ends_with(".git")
+}
+
+// template repos are urls and local bare repositories, other directories are used as they are
+pub fn is_template_repo(location: &str) -> bool {
+    let path = Path::new(location);
+    if path.is_dir() {
+        return git_is_bare(location);
+    }
+    !path.exists() && is_git_url(location)
+}
+
+// repo#dir picks a template out of a repo holding several
+pub fn split_template_dir(template: &str) -> (&str, Option<&str>) {
+    if Path::new(template).exists() {
+        return (template, None);
+    }
+    match template.rsplit_once('#') {
+        Some((repo, dir)) if !dir.is_empty() => (repo, Some(dir)),
+        _ => (template, None),
+    }
+}
+
+// clone of repo under cache, brought up to date when it was cloned before
+pub fn checkout_template_repo(repo: &str, cache: &Path) -> Result<PathBuf> {
+    // a local repo is the same one from wherever cot runs
+    let repo = match fs::canonicalize(repo) {
+        Ok(path) => path.to_string_lossy().to_string(),
+        Err(_) => repo.to_string(),
+    };
+    if !git_installed() {
+        return Err(anyhow!(
+            "git is needed for template {}, install it first",
+            repo
+        ));
+    }
+    let checkout = cache.join(&sha256_str(&repo)[..16]);
+    let has_clone = checkout.join(".git").is_dir();
+    if has_clone && !git_dir_is_repo(&checkout.to_string_lossy()) {
+        // an interrupted clone or a broken one, clone it again
+        warn!("cloning {} again, {} is broken", repo, checkout.display());
+        fs::remove_dir_all(&checkout)?;
+    } else if has_clone {
+        debug!("updating {} in {}", repo, checkout.display());
+        if let Err(e) = git_sync(&checkout.to_string_lossy()) {
+            // offline, or the repo is gone: the last clone still does
+            warn!("using the cached clone of {}: {:?}", repo, e);
+        }
+        return Ok(checkout);
+    }
+    fs::create_dir_all(cache)
+        .with_context(|| format!("Failed to create template cache {}", cache.display()))?;
+    // clone into a temp dir first so a failed clone leaves nothing behind
+    let staging = tempfile::tempdir_in(cache)?;
+    let cloned = staging.path().join("repo");
+    debug!("cloning {} into {}", repo, checkout.display());
+    git_clone(&repo, &cloned.to_string_lossy())
+        .map_err(|e| anyhow!("Failed to clone template {}: {}", repo, e))?;
+    fs::rename(&cloned, &checkout)?;
+    Ok(checkout)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::util::git_run;
+
+    // bare repo at <dir>/templates.git with files committed, as `git push` would leave it
+    pub(crate) fn make_template_repo(dir: &Path, files: &[(&str, &str)]) -> PathBuf {
+        let work = dir.join("work");
+        for (path, content) in files {
+            let path = work.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        let work = work.to_string_lossy().to_string();
+        git_run(&["init", "--quiet", &work]).unwrap();
+        git_run(&["-C", &work, "add", "."]).unwrap();
+        git_run(&[
+            "-C",
+            &work,
+            "-c",
+            "user.name=cot",
+            "-c",
+            "user.email=cot@localhost",
+            "commit",
+            "--quiet",
+            "-m",
+            "templates",
+        ])
+        .unwrap();
+        let bare = dir.join("templates.git");
+        git_run(&["clone", "--quiet", "--bare", &work, &bare.to_string_lossy()]).unwrap();
+        bare
+    }
+
+    #[test]
+    fn test_checkout_template_repo() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = tempfile::tempdir().unwrap();
+        let bare = make_template_repo(dir.path(), &[("rust/README.md", "# {{name}}")]);
+        let bare = bare.to_str().unwrap();
+        assert!(is_template_repo(bare));
+        assert!(!is_template_repo(dir.path().to_str().unwrap()));
+        assert!(is_git_url("git@github.com:team/templates.git"));
+        assert!(is_git_url("https://example.com/team/templates"));
+        assert_eq!(
+            split_template_dir("https://example.com/t.git#rust"),
+            ("https://example.com/t.git", Some("rust"))
+        );
+
+        let checkout = checkout_template_repo(bare, cache.path()).unwrap();
+        assert!(checkout.join("rust").join("README.md").is_file());
+        // the second time it updates the same clone
+        fs::write(checkout.join("rust").join("README.md"), "changed").unwrap();
+        assert_eq!(
+            checkout_template_repo(bare, cache.path()).unwrap(),
+            checkout
+        );
+        assert_eq!(
+            fs::read_to_string(checkout.join("rust").join("README.md")).unwrap(),
+            "# {{name}}"
+        );
+        assert_eq!(fs::read_dir(cache.path()).unwrap().count(), 1);
+
+        // a broken clone is cloned again
+        fs::remove_dir_all(checkout.join(".git")).unwrap();
+        fs::create_dir(checkout.join(".git")).unwrap();
+        fs::remove_dir_all(checkout.join("rust")).unwrap();
+        assert_eq!(
+            checkout_template_repo(bare, cache.path()).unwrap(),
+            checkout
+        );
+        assert!(checkout.join("rust").join("README.md").is_file());
+    }
+}
@@ -0,0 +1,344 @@
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::init::{render_template, InitOptions, InitRecord, TemplateDir};
+use crate::util::git_merge_file;
+
+// the template's version of a binary file both sides changed, next to the project's
+pub const TEMPLATE_COPY_SUFFIX: &str = ".template";
+
+/// What `cot init --update` did to the project, paths relative to it.
+#[derive(Debug, Default)]
+pub struct UpdateReport {
+    pub from: String,
+    pub to: String,
+    // rewritten with the template's changes, merged or not
+    pub updated: Vec<PathBuf>,
+    pub added: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+    // removed from the template but changed in the project, so left alone
+    pub kept: Vec<PathBuf>,
+    // merged with conflict markers and how many, or binary with a .template copy
+    pub conflicts: Vec<(PathBuf, usize)>,
+    // the template's versions of the binary conflicts
+    pub copies: Vec<PathBuf>,
+}
+
+impl UpdateReport {
+    pub fn up_to_date(&self) -> bool {
+        self.from == self.to
+    }
+}
+
+// `cot init --update`: the template's changes since the project was created, merged into it
+pub fn update_project(project: &Path, options: &InitOptions) -> Result<UpdateReport> {
+    let record = InitRecord::load(project)?;
+    let from = record.revision.clone().ok_or_else(|| {
+        anyhow!(
+            "{} has no revisions, only templates in git repositories can be updated",
+            record.template
+        )
+    })?;
+    let template = TemplateDir::open(&record.template, options.cache.as_deref())?;
+    let to = match &template.git {
+        Some(git) => git.revision.clone(),
+        None => return Err(anyhow!("{} is no longer a git repository", record.template)),
+    };
+    let mut report = UpdateReport {
+        from: from.clone(),
+        to: to.clone(),
+        ..Default::default()
+    };
+    if from == to {
+        return Ok(report);
+    }
+    let name = match record.vars.get("name") {
+        Some(name) => name.clone(),
+        None => project_name(project)?,
+    };
+
+    // the project as the old template rendered it, with the same vars
+    let base_options = InitOptions {
+        vars: record.vars.clone(),
+        interactive: false,
+        ..Default::default()
+    };
+    let base_dir = tempfile::tempdir()?;
+    let (base_files, _) = render_template(
+        &template.at_revision(&from)?,
+        &name,
+        base_dir.path(),
+        &base_options,
+    )?;
+    // new variables of the template are asked for, --set overrides the recorded ones
+    let mut vars = record.vars.clone();
+    vars.extend(options.vars.clone());
+    let new_options = InitOptions {
+        vars,
+        ..options.clone()
+    };
+    let new_dir = tempfile::tempdir()?;
+    let (new_files, vars) = render_template(&template, &name, new_dir.path(), &new_options)?;
+
+    // everything is worked out before the project is touched, a failing merge leaves it as it was
+    let base_files: BTreeSet<PathBuf> = base_files.into_iter().collect();
+    let new_files: BTreeSet<PathBuf> = new_files.into_iter().collect();
+    let mut changes = vec![];
+    for file in base_files.union(&new_files) {
+        let base = base_dir.path().join(file);
+        let theirs = new_dir.path().join(file);
+        let ours = project.join(file);
+        match (base_files.contains(file), new_files.contains(file)) {
+            (true, false) => {
+                if !ours.exists() {
+                    continue;
+                }
+                if same_content(&ours, &base)? {
+                    changes.push(Change::Remove(ours));
+                    report.removed.push(file.clone());
+                } else {
+                    report.kept.push(file.clone());
+                }
+            }
+            (false, true) if !ours.exists() => {
+                changes.push(Change::Copy(theirs, ours));
+                report.added.push(file.clone());
+            }
+            (false, true) => {
+                // the project made one itself, merge both as if from an empty file
+                fs::write(&base, "")?;
+                changes.push(merge(&ours, &base, &theirs, file, &mut report)?);
+            }
+            _ => {
+                if same_content(&base, &theirs)? || !ours.exists() {
+                    continue;
+                }
+                if same_content(&ours, &base)? {
+                    changes.push(Change::Copy(theirs, ours));
+                    report.updated.push(file.clone());
+                } else {
+                    changes.push(merge(&ours, &base, &theirs, file, &mut report)?);
+                }
+            }
+        }
+    }
+
+    for change in changes {
+        match change {
+            // the template's file as it is, keeping its permissions
+            Change::Copy(from, path) => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::copy(&from, &path)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+            }
+            Change::Write(path, data) => {
+                fs::write(&path, data)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+            }
+            Change::Remove(path) => {
+                debug!("removing {}", path.display());
+                fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove {}", path.display()))?;
+            }
+        }
+    }
+    // the actions already ran when the project was created
+    let record = InitRecord {
+        revision: Some(to),
+        vars,
+        ..record
+    };
+    record.save(project)?;
+    Ok(report)
+}
+
+// what the update does to one file of the project
+enum Change {
+    Copy(PathBuf, PathBuf),
+    Write(PathBuf, Vec<u8>),
+    Remove(PathBuf),
+}
+
+fn project_name(project: &Path) -> Result<String> {
+    let project = fs::canonicalize(project)?;
+    let name = project
+        .file_name()
+        .ok_or_else(|| anyhow!("no project name in {}", project.display()))?;
+    Ok(name.to_string_lossy().to_string())
+}
+
+fn same_content(a: &Path, b: &Path) -> Result<bool> {
+    Ok(fs::read(a)? == fs::read(b)?)
+}
+
+fn is_binary(path: &Path) -> Result<bool> {
+    let data = fs::read(path)?;
+    Ok(data.contains(&0) || std::str::from_utf8(&data).is_err())
+}
+
+// git merge-file for text, binary files changed on both sides keep the project's version
+// with the template's next to it as <file>.template
+fn merge(
+    ours: &Path,
+    base: &Path,
+    theirs: &Path,
+    file: &Path,
+    report: &mut UpdateReport,
+) -> Result<Change> {
+    if is_binary(ours)? || is_binary(base)? || is_binary(theirs)? {
+        let mut copy = ours.as_os_str().to_owned();
+        copy.push(TEMPLATE_COPY_SUFFIX);
+        report.conflicts.push((file.to_path_buf(), 1));
+        let mut relative = file.as_os_str().to_owned();
+        relative.push(TEMPLATE_COPY_SUFFIX);
+        report.copies.push(PathBuf::from(relative));
+        return Ok(Change::Copy(theirs.to_path_buf(), PathBuf::from(copy)));
+    }
+    let path = |path: &Path| path.to_string_lossy().to_string();
+    let (merged, conflicts) = git_merge_file(&path(ours), &path(base), &path(theirs))?;
+    report.updated.push(file.to_path_buf());
+    if conflicts > 0 {
+        report.conflicts.push((file.to_path_buf(), conflicts));
+    }
+    Ok(Change::Write(ours.to_path_buf(), merged.into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::init::init_project;
+    use crate::template::repo::tests::make_template_repo;
+    use crate::util::git_run;
+
+    // commits files to the work repo next to the bare one and pushes them
+    fn push_template(dir: &Path, files: &[(&str, Option<&str>)]) {
+        let work = dir.join("work");
+        for (path, content) in files {
+            match content {
+                Some(content) => fs::write(work.join(path), content).unwrap(),
+                None => fs::remove_file(work.join(path)).unwrap(),
+            }
+        }
+        let work = work.to_string_lossy().to_string();
+        git_run(&["-C", &work, "add", "-A"]).unwrap();
+        git_run(&[
+            "-C",
+            &work,
+            "-c",
+            "user.name=cot",
+            "-c",
+            "user.email=cot@localhost",
+            "commit",
+            "--quiet",
+            "-m",
+            "update",
+        ])
+        .unwrap();
+        let bare = dir.join("templates.git").to_string_lossy().to_string();
+        git_run(&["-C", &work, "push", "--quiet", &bare, "HEAD"]).unwrap();
+    }
+
+    #[test]
+    fn test_update_project() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = tempfile::tempdir().unwrap();
+        let out = tempfile::tempdir().unwrap();
+        let bare = make_template_repo(
+            dir.path(),
+            &[
+                ("py/README.md", "# {{name}}\n\nabout\n\nusage\n\nend\n"),
+                (
+                    "py/setup.cfg",
+                    "[metadata]\nname = {{name}}\nversion = 0.1\n",
+                ),
+                ("py/old.txt", "old\n"),
+                ("py/kept.txt", "kept\n"),
+                ("py/logo.bin", "logo\0v1"),
+            ],
+        );
+        let options = InitOptions {
+            cache: Some(cache.path().to_path_buf()),
+            ..Default::default()
+        };
+        let template = format!("{}#py", bare.display());
+        let dest = out.path().join("app");
+        init_project(&template, &dest, &options).unwrap();
+        let record = InitRecord::load(&dest).unwrap();
+        assert!(record.revision.is_some());
+        assert_eq!(record.vars["name"], "app");
+        assert!(update_project(&dest, &options).unwrap().up_to_date());
+
+        // the project edits some files, the template others and one the same
+        fs::write(
+            dest.join("README.md"),
+            "# app\n\nabout the app\n\nusage\n\nend\n",
+        )
+        .unwrap();
+        fs::write(
+            dest.join("setup.cfg"),
+            "[metadata]\nname = app\nversion = 0.2\n",
+        )
+        .unwrap();
+        fs::write(dest.join("kept.txt"), "mine\n").unwrap();
+        fs::write(dest.join("logo.bin"), "logo\0mine").unwrap();
+        push_template(
+            dir.path(),
+            &[
+                (
+                    "py/README.md",
+                    Some("# {{name}}\n\nabout\n\nusage\n\nlicense\n"),
+                ),
+                (
+                    "py/setup.cfg",
+                    Some("[metadata]\nname = {{name}}\nversion = 1.0\n"),
+                ),
+                ("py/new.txt", Some("new {{name}}\n")),
+                ("py/old.txt", None),
+                ("py/kept.txt", None),
+                ("py/logo.bin", Some("logo\0v2")),
+            ],
+        );
+
+        let report = update_project(&dest, &options).unwrap();
+        assert!(!report.up_to_date());
+        assert_eq!(
+            fs::read_to_string(dest.join("README.md")).unwrap(),
+            "# app\n\nabout the app\n\nusage\n\nlicense\n"
+        );
+        assert_eq!(
+            report.conflicts,
+            vec![
+                (PathBuf::from("logo.bin"), 1),
+                (PathBuf::from("setup.cfg"), 1)
+            ]
+        );
+        // binary files aren't merged, the template's version is put next to the project's
+        assert_eq!(report.copies, vec![PathBuf::from("logo.bin.template")]);
+        assert_eq!(fs::read(dest.join("logo.bin")).unwrap(), b"logo\0mine");
+        assert_eq!(
+            fs::read(dest.join("logo.bin.template")).unwrap(),
+            b"logo\0v2"
+        );
+        assert!(fs::read_to_string(dest.join("setup.cfg"))
+            .unwrap()
+            .contains("<<<<<<< project"));
+        assert_eq!(report.added, vec![PathBuf::from("new.txt")]);
+        assert_eq!(
+            fs::read_to_string(dest.join("new.txt")).unwrap(),
+            "new app\n"
+        );
+        assert_eq!(report.removed, vec![PathBuf::from("old.txt")]);
+        assert_eq!(report.kept, vec![PathBuf::from("kept.txt")]);
+        assert!(dest.join("kept.txt").exists());
+        assert_eq!(
+            InitRecord::load(&dest).unwrap().revision.as_ref(),
+            Some(&report.to)
+        );
+        assert!(update_project(&dest, &options).unwrap().up_to_date());
+    }
+}
@@ -26,7 +26,129 @@ pub fn git_dir_is_repo(path: &str) -> bool {
         .arg(path)
         .arg("rev-parse")
         .output();
-    output.is_ok()
+    matches!(output, Ok(output) if output.status.success())
+}
+
+pub fn git_is_bare(path: &str) -> bool {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(path)
+        .arg("rev-parse")
+        .arg("--is-bare-repository")
+        .output();
+    matches!(output, Ok(output) if output.status.success() && output.stdout.starts_with(b"true"))
+}
+
+// run git with args, its stdout on success and its stderr in the error otherwise
+pub fn git_run(args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to run git {}", args.join(" ")))?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+pub fn git_clone(url: &str, dest: &str) -> Result<()> {
+    git_run(&["clone", "--quiet", url, dest])?;
+    Ok(())
+}
+
+// bring a clone to what its origin has now, dropping local changes
+pub fn git_sync(dir: &str) -> Result<()> {
+    git_run(&["-C", dir, "fetch", "--quiet", "--prune", "origin"])?;
+    git_run(&["-C", dir, "reset", "--quiet", "--hard", "origin/HEAD"])?;
+    Ok(())
+}
+
+pub fn git_init(dir: &str) -> Result<()> {
+    git_run(&["init", "--quiet", dir])?;
+    Ok(())
+}
+
+pub fn git_commit_all(dir: &str, message: &str) -> Result<()> {
+    git_run(&["-C", dir, "add", "--all"])?;
+    git_run(&["-C", dir, "commit", "--quiet", "--message", message])?;
+    Ok(())
+}
+
+pub fn git_tag_in(dir: &str, tag: &str) -> Result<()> {
+    git_run(&["-C", dir, "tag", tag])?;
+    Ok(())
+}
+
+pub fn git_head_of(dir: &str) -> Result<String> {
+    git_run(&["-C", dir, "rev-parse", "HEAD"])
+}
+
+// the tree of dir's repo at rev, written out into dest
+pub fn git_archive(dir: &str, rev: &str, dest: &str) -> Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let tar_file = temp_dir.path().join("archive.tar");
+    git_run(&[
+        "-C",
+        dir,
+        "archive",
+        "--format=tar",
+        "--output",
+        &tar_file.to_string_lossy(),
+        rev,
+    ])?;
+    super::extract(&tar_file.to_string_lossy(), dest)
+}
+
+// three-way merge of ours and theirs from base, the merged text and how many conflicts it has
+pub fn git_merge_file(ours: &str, base: &str, theirs: &str) -> Result<(String, usize)> {
+    let output = Command::new("git")
+        .args([
+            "merge-file",
+            "--stdout",
+            "-L",
+            "project",
+            "-L",
+            "base",
+            "-L",
+            "template",
+        ])
+        .args([ours, base, theirs])
+        .output()
+        .with_context(|| format!("Failed to run git merge-file on {}", ours))?;
+    // the exit code counts conflicts, negative on errors
+    let conflicts = match output.status.code() {
+        Some(code) if (0..128).contains(&code) => code as usize,
+        _ => {
+            return Err(anyhow::anyhow!(
+                "git merge-file {} failed: {}",
+                ours,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    };
+    Ok((
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        conflicts,
+    ))
+}
+
+pub fn git_toplevel(path: &str) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(path)
+        .arg("rev-parse")
+        .arg("--show-toplevel")
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let s = str::from_utf8(&output.stdout).ok()?;
+    Some(s.trim().to_string())
 }
 
 pub fn git_add_tag(tag: &str) -> Result<String> {
@@ -122,11 +244,41 @@ mod tests {
         assert!(git_dir_is_repo("."));
     }
 
+    #[test]
+    fn test_git_toplevel() {
+        let toplevel = git_toplevel("src").unwrap();
+        assert!(std::path::Path::new(&toplevel).join("Cargo.toml").is_file());
+        let dir = tempfile::tempdir().unwrap();
+        assert!(!git_dir_is_repo(dir.path().to_str().unwrap()));
+        assert_eq!(git_toplevel(dir.path().to_str().unwrap()), None);
+    }
+
+    #[test]
+    fn test_git_merge_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, text: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, text).unwrap();
+            path.to_string_lossy().to_string()
+        };
+        let base = write("base", "a\nb\nc\n");
+        let ours = write("ours", "a\nB\nc\n");
+        let theirs = write("theirs", "a\nb\nc\nd\n");
+        assert_eq!(
+            git_merge_file(&ours, &base, &theirs).unwrap(),
+            ("a\nB\nc\nd\n".to_string(), 0)
+        );
+        let theirs = write("theirs", "a\nbb\nc\n");
+        let (merged, conflicts) = git_merge_file(&ours, &base, &theirs).unwrap();
+        assert_eq!(conflicts, 1);
+        assert!(merged.contains("<<<<<<< project"));
+    }
+
     #[test]
     fn test_git_get_all_tags() {
         let tags = git_all_tags().unwrap();
         assert_ne!(tags, Vec::<String>::new());
-        assert!(tags.len() > 0);
+        assert!(!tags.is_empty());
     }
 
     #[test]
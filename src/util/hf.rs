@@ -1,10 +1,14 @@
 use anyhow::{Context, Result};
 use log::debug;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
 use std::env;
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
 
+use super::size::dir_size;
+
 fn get_metadata(path: &str) -> Result<fs::Metadata> {
     fs::metadata(path).with_context(|| format!("Failed to get metadata for {}", path))
 }
@@ -67,6 +71,18 @@ pub fn huggingface_hub_cache() -> Result<String> {
     Ok(cache)
 }
 
+// where huggingface_hub_cache() is, whether anything was downloaded into it yet or not
+pub fn hf_hub_cache_dir() -> Result<String> {
+    if let Ok(cache) = env::var("HUGGINGFACE_HUB_CACHE") {
+        return Ok(cache);
+    }
+    let hf_home = match env::var("HF_HOME") {
+        Ok(hf_home) => PathBuf::from(hf_home),
+        Err(_) => expand_path("~/.cache/huggingface")?,
+    };
+    Ok(hf_home.join("hub").to_string_lossy().to_string())
+}
+
 pub fn hf_datasets_cache() -> Result<String> {
     let cache = if env::var("HUGGINGFACE_HUB_CACHE").is_ok() {
         get_dir_with_env("HF_DATASETS_CACHE", "~/.cache/huggingface/datasets")?
@@ -114,13 +130,319 @@ pub fn hf_datasets_path(model: &str) -> Result<String> {
 
     Ok(result.to_str().unwrap_or("").to_string())
 }
+// ids of the `kind` repos (models, datasets) in the hub cache, models--org--name => org/name
+pub fn hf_cached_ids(cache: &str, kind: &str) -> Result<Vec<String>> {
+    let prefix = format!("{}--", kind);
+    let mut ids = vec![];
+    // nothing downloaded yet
+    if !Path::new(cache).exists() {
+        return Ok(ids);
+    }
+    for entry in fs::read_dir(cache).with_context(|| format!("Failed to read {}", cache))? {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        if let Some(repo) = name.strip_prefix(&prefix) {
+            ids.push(repo.replace("--", "/"));
+        }
+    }
+    ids.sort();
+    Ok(ids)
+}
+
+// the repo types of the hub cache, stored as <type>s--org--name
+pub const HF_REPO_TYPES: [&str; 3] = ["model", "dataset", "space"];
+
+/// A model, dataset or space in the hub cache.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HfCachedRepo {
+    pub repo_type: String,
+    pub id: String,
+    // main, pr/1 and other refs => the commit they point at
+    pub refs: BTreeMap<String, String>,
+    pub snapshots: usize,
+    // bytes of its blobs, the snapshots only link to them
+    pub size: u64,
+    pub path: PathBuf,
+}
+
+// every repo in the hub cache, by type then id
+pub fn hf_cached_repos(cache: &str) -> Result<Vec<HfCachedRepo>> {
+    let mut repos = vec![];
+    for repo_type in HF_REPO_TYPES {
+        for id in hf_cached_ids(cache, &format!("{}s", repo_type))? {
+            let path = Path::new(cache).join(format!("{}s--{}", repo_type, id.replace('/', "--")));
+            let mut refs = BTreeMap::new();
+            read_refs(&path.join("refs"), "", &mut refs)?;
+            let snapshots = match fs::read_dir(path.join("snapshots")) {
+                Ok(entries) => entries.count(),
+                Err(_) => 0,
+            };
+            repos.push(HfCachedRepo {
+                repo_type: repo_type.to_string(),
+                id,
+                refs,
+                snapshots,
+                size: dir_size(&path.join("blobs")),
+                path,
+            });
+        }
+    }
+    Ok(repos)
+}
+
+/// Disk usage of a cached repo with each blob counted once.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HfRepoSize {
+    pub repo_type: String,
+    pub id: String,
+    pub size: u64,
+    // bytes of blobs in more than one revision
+    pub shared: u64,
+    // bytes of blobs no revision links to any more
+    pub unreferenced: u64,
+    pub revisions: Vec<HfRevisionSize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HfRevisionSize {
+    pub commit: String,
+    pub refs: Vec<String>,
+    pub files: usize,
+    // its blobs, whether other revisions link them too or not
+    pub size: u64,
+    // what of size other revisions use too, deleting it frees size - shared
+    pub shared: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HfCacheSize {
+    pub path: PathBuf,
+    pub size: u64,
+    pub repos: Vec<HfRepoSize>,
+}
+
+// every repo of the hub cache and its revisions, snapshots link into blobs/ so a blob is counted once
+pub fn hf_cache_size(cache: &str) -> Result<HfCacheSize> {
+    let repos = hf_cached_repos(cache)?
+        .iter()
+        .map(hf_repo_size)
+        .collect::<Result<Vec<_>>>()?;
+    Ok(HfCacheSize {
+        path: PathBuf::from(cache),
+        size: repos.iter().map(|repo| repo.size).sum(),
+        repos,
+    })
+}
+
+pub fn hf_repo_size(repo: &HfCachedRepo) -> Result<HfRepoSize> {
+    // blob => its bytes and the revisions linking it
+    let mut blobs: BTreeMap<PathBuf, (u64, BTreeSet<String>)> = BTreeMap::new();
+    if let Ok(entries) = fs::read_dir(repo.path.join("blobs")) {
+        for entry in entries {
+            let path = fs::canonicalize(entry?.path())?;
+            let size = fs::metadata(&path)?.len();
+            blobs.insert(path, (size, BTreeSet::new()));
+        }
+    }
+    let mut commits = vec![];
+    if let Ok(entries) = fs::read_dir(repo.path.join("snapshots")) {
+        for entry in entries {
+            let entry = entry?;
+            let commit = entry.file_name().to_string_lossy().to_string();
+            let mut files = vec![];
+            snapshot_files(&entry.path(), &mut files)?;
+            for file in &files {
+                let size = fs::metadata(file)?.len();
+                blobs
+                    .entry(file.clone())
+                    .or_insert((size, BTreeSet::new()))
+                    .1
+                    .insert(commit.clone());
+            }
+            commits.push((commit, files.len()));
+        }
+    }
+    commits.sort();
+
+    let revisions = commits
+        .into_iter()
+        .map(|(commit, files)| {
+            let linked = blobs.values().filter(|(_, revs)| revs.contains(&commit));
+            let (size, shared) = linked.fold((0, 0), |(size, shared), (bytes, revs)| {
+                let other = if revs.len() > 1 { *bytes } else { 0 };
+                (size + bytes, shared + other)
+            });
+            HfRevisionSize {
+                refs: repo
+                    .refs
+                    .iter()
+                    .filter(|(_, target)| **target == commit)
+                    .map(|(name, _)| name.clone())
+                    .collect(),
+                commit,
+                files,
+                size,
+                shared,
+            }
+        })
+        .collect();
+    let sum = |keep: &dyn Fn(usize) -> bool| -> u64 {
+        blobs
+            .values()
+            .filter(|(_, revs)| keep(revs.len()))
+            .map(|(bytes, _)| bytes)
+            .sum()
+    };
+    Ok(HfRepoSize {
+        repo_type: repo.repo_type.clone(),
+        id: repo.id.clone(),
+        size: sum(&|_| true),
+        shared: sum(&|revs| revs > 1),
+        unreferenced: sum(&|revs| revs == 0),
+        revisions,
+    })
+}
+
+// the files of a snapshot resolved to the blobs they link, or themselves where links aren't used
+fn snapshot_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        // a dangling link has nothing on disk
+        let Ok(resolved) = fs::canonicalize(&path) else {
+            continue;
+        };
+        if resolved.is_dir() {
+            snapshot_files(&path, files)?;
+        } else {
+            files.push(resolved);
+        }
+    }
+    Ok(())
+}
+
+// refs/main and refs/pr/1, a repo downloaded by commit has no refs
+fn read_refs(dir: &Path, prefix: &str, refs: &mut BTreeMap<String, String>) -> Result<()> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(());
+    };
+    for entry in entries {
+        let entry = entry?;
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        if entry.file_type()?.is_dir() {
+            read_refs(&entry.path(), &format!("{}/", name), refs)?;
+        } else {
+            refs.insert(name, fs::read_to_string(entry.path())?.trim().to_string());
+        }
+    }
+    Ok(())
+}
+
 fn read_oid_of(model_or_ds: &Path) -> Result<String, io::Error> {
     let file_path = model_or_ds.join("refs").join("main");
     match fs::read_to_string(file_path.clone()) {
         Ok(data) => Ok(data.trim().to_string()),
-        Err(err) => Err(io::Error::new(
-            io::ErrorKind::Other,
-            format!("Failed to read OID from {}: {}", file_path.display(), err),
-        )),
+        Err(err) => Err(io::Error::other(format!(
+            "Failed to read OID from {}: {}",
+            file_path.display(),
+            err
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hf_cached_ids() {
+        let cache = tempfile::tempdir().unwrap();
+        for dir in [
+            "models--baai--bge-large-zh-v1.5",
+            "models--gpt2",
+            "datasets--org--ds",
+            ".locks",
+        ] {
+            fs::create_dir(cache.path().join(dir)).unwrap();
+        }
+        let cache = cache.path().to_str().unwrap();
+        assert_eq!(
+            hf_cached_ids(cache, "models").unwrap(),
+            vec!["baai/bge-large-zh-v1.5", "gpt2"]
+        );
+        assert_eq!(hf_cached_ids(cache, "datasets").unwrap(), vec!["org/ds"]);
+
+        let missing = Path::new(cache).join("missing");
+        let missing = missing.to_str().unwrap();
+        assert!(hf_cached_ids(missing, "models").unwrap().is_empty());
+        assert!(hf_cache_size(missing).unwrap().repos.is_empty());
+    }
+
+    #[test]
+    fn test_hf_cached_repos() {
+        let cache = tempfile::tempdir().unwrap();
+        let repo = cache.path().join("models--org--bert");
+        fs::create_dir_all(repo.join("blobs")).unwrap();
+        fs::create_dir_all(repo.join("refs").join("pr")).unwrap();
+        fs::create_dir_all(repo.join("snapshots").join("abc")).unwrap();
+        fs::create_dir_all(repo.join("snapshots").join("def")).unwrap();
+        fs::write(repo.join("blobs").join("1234"), "weights").unwrap();
+        fs::write(repo.join("refs").join("main"), "abc\n").unwrap();
+        fs::write(repo.join("refs").join("pr").join("1"), "def").unwrap();
+        fs::create_dir(cache.path().join("spaces--org--demo")).unwrap();
+
+        let repos = hf_cached_repos(cache.path().to_str().unwrap()).unwrap();
+        assert_eq!(repos.len(), 2);
+        assert_eq!(repos[0].repo_type, "model");
+        assert_eq!(repos[0].id, "org/bert");
+        assert_eq!(repos[0].refs["main"], "abc");
+        assert_eq!(repos[0].refs["pr/1"], "def");
+        assert_eq!(repos[0].snapshots, 2);
+        assert_eq!(repos[0].size, 7);
+        assert_eq!(repos[1].repo_type, "space");
+        assert_eq!(repos[1].size, 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hf_cache_size() {
+        use std::os::unix::fs::symlink;
+
+        let cache = tempfile::tempdir().unwrap();
+        let repo = cache.path().join("models--org--bert");
+        fs::create_dir_all(repo.join("blobs")).unwrap();
+        fs::create_dir_all(repo.join("refs")).unwrap();
+        fs::create_dir_all(repo.join("snapshots").join("old").join("onnx")).unwrap();
+        fs::create_dir_all(repo.join("snapshots").join("new")).unwrap();
+        fs::write(repo.join("refs").join("main"), "new").unwrap();
+        for (blob, size) in [
+            ("config", 10),
+            ("weights1", 1000),
+            ("weights2", 2000),
+            ("gone", 5),
+        ] {
+            fs::write(repo.join("blobs").join(blob), vec![0u8; size]).unwrap();
+        }
+        let link = |blob: &str, file: &str| {
+            symlink(
+                repo.join("blobs").join(blob),
+                repo.join("snapshots").join(file),
+            )
+            .unwrap()
+        };
+        link("config", "old/config.json");
+        link("weights1", "old/onnx/model.onnx");
+        link("config", "new/config.json");
+        link("weights2", "new/model.bin");
+
+        let size = hf_cache_size(cache.path().to_str().unwrap()).unwrap();
+        assert_eq!(size.size, 3015);
+        let repo = &size.repos[0];
+        assert_eq!((repo.size, repo.shared, repo.unreferenced), (3015, 10, 5));
+        let new = &repo.revisions[0];
+        assert_eq!(new.commit, "new");
+        assert_eq!(new.refs, vec!["main"]);
+        assert_eq!((new.files, new.size, new.shared), (2, 2010, 10));
+        let old = &repo.revisions[1];
+        assert!(old.refs.is_empty());
+        assert_eq!((old.files, old.size, old.shared), (2, 1010, 10));
     }
 }
@@ -1,20 +1,24 @@
+mod digest;
 mod git;
 mod hf;
 mod repl;
+mod size;
 mod unzip;
 mod ver;
 mod xf;
 
+pub use self::digest::*;
 pub use self::git::*;
 pub use self::hf::*;
 pub use self::repl::*;
+pub use self::size::*;
 pub use self::unzip::*;
 pub use self::ver::*;
 pub use self::xf::*;
 
 use dirs::home_dir;
 
-fn expand_tilde(path: &str) -> Option<String> {
+pub fn expand_tilde(path: &str) -> Option<String> {
     let home = home_dir();
     if path == "~" || path == "~/" {
         home.map(|home| format!("{}", home.to_string_lossy()))
@@ -1,6 +1,12 @@
+use std::collections::HashMap;
 use std::env;
 
-pub fn replace_envs(input: &str) -> String {
+// $NAME, ${NAME} and %NAME% from the environment, or with vars only ${NAME} for the names
+// in vars, so shell code such as `$f;` or `date +%Y-%m-%d` passes through untouched
+pub fn replace_envs(input: &str, vars: Option<&HashMap<String, String>>) -> String {
+    if let Some(vars) = vars {
+        return replace_vars(input, vars);
+    }
     let mut output = String::new();
     let mut i = 0;
     while i < input.len() {
@@ -74,6 +80,30 @@ pub fn replace_envs(input: &str) -> String {
     output
 }
 
+fn replace_vars(input: &str, vars: &HashMap<String, String>) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find("${") {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let known = after
+            .find('}')
+            .and_then(|end| vars.get(&after[..end]).map(|value| (end, value)));
+        match known {
+            Some((end, value)) => {
+                output.push_str(value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                output.push_str("${");
+                rest = after;
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -81,7 +111,7 @@ mod tests {
     #[test]
     fn test_one_env() {
         env::set_var("TEST_ENV", "test");
-        assert_eq!(replace_envs("This is a $TEST_ENV"), "This is a test");
+        assert_eq!(replace_envs("This is a $TEST_ENV", None), "This is a test");
     }
 
     #[test]
@@ -90,8 +120,22 @@ mod tests {
         env::set_var("ANOTHER_ENV", "another");
         env::set_var("YET_ANOTHER_ENV", "yet another");
         assert_eq!(
-            replace_envs("This is a ${TEST_ENV} and $ANOTHER_ENV and %YET_ANOTHER_ENV%"),
+            replace_envs(
+                "This is a ${TEST_ENV} and $ANOTHER_ENV and %YET_ANOTHER_ENV%",
+                None
+            ),
             "This is a test and another and yet another"
         );
     }
+
+    #[test]
+    fn test_replace_vars_only() {
+        let vars = HashMap::from([("PLUGIN_DIR".to_string(), "/opt/nöde".to_string())]);
+        assert_eq!(
+            replace_envs("cp ${PLUGIN_DIR}/bin ${HOME}/${UNCLOSED", Some(&vars)),
+            "cp /opt/nöde/bin ${HOME}/${UNCLOSED"
+        );
+        let script = "for f in a b; do echo $f; done; date +%Y-%m-%d; echo héllo %PATH%";
+        assert_eq!(replace_envs(script, Some(&vars)), script);
+    }
 }
@@ -0,0 +1,87 @@
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::Path;
+
+// bytes of the files under path, symlinks are not followed
+pub fn dir_size(path: &Path) -> u64 {
+    let Ok(metadata) = path.symlink_metadata() else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| dir_size(&entry.path()))
+        .sum()
+}
+
+const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+
+// 1536 => 1.5 KB
+pub fn format_size(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+// 500M, 2G, 1.5GB, 1024 => bytes
+pub fn parse_size(size: &str) -> Result<u64> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| anyhow!("invalid size {:?}", size))?;
+    let unit = unit.trim().to_uppercase();
+    let unit = unit.strip_suffix('B').unwrap_or(&unit);
+    let power = match unit {
+        "" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        _ => {
+            return Err(anyhow!(
+                "invalid size {:?}, use a unit of B, K, M, G or T",
+                size
+            ))
+        }
+    };
+    Ok((number * 1024f64.powi(power)) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GB");
+        assert_eq!(parse_size("1024").unwrap(), 1024);
+        assert_eq!(parse_size("500M").unwrap(), 500 * 1024 * 1024);
+        assert_eq!(parse_size("1.5gb").unwrap(), 3 * 512 * 1024 * 1024);
+        assert!(parse_size("10 parsecs").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("a"), "abc").unwrap();
+        fs::write(dir.path().join("sub").join("b"), "de").unwrap();
+        assert_eq!(dir_size(dir.path()), 5);
+    }
+}
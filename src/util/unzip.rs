@@ -1,5 +1,4 @@
 use std::fs::File;
-use std::io;
 use std::path::PathBuf;
 use zip::ZipArchive;
 
@@ -11,8 +10,16 @@ pub fn unzip(zip_path: &str, dest: &str) -> anyhow::Result<()> {
     for i in 0..archive.len() {
         let mut entry = archive.by_index(i)?;
 
+        // entries such as ../evil or /etc/passwd would land outside dest
+        let Some(name) = entry.enclosed_name() else {
+            return Err(anyhow::anyhow!(
+                "{} has an entry outside the archive: {}",
+                zip_path,
+                entry.name()
+            ));
+        };
         let mut extract_path = PathBuf::from(dest);
-        extract_path.push(entry.name());
+        extract_path.push(name);
 
         if let Some(parent) = extract_path.parent() {
             std::fs::create_dir_all(parent)?;
@@ -20,8 +27,14 @@ pub fn unzip(zip_path: &str, dest: &str) -> anyhow::Result<()> {
 
         if !entry.is_dir() {
             // println!("Extracting: {}", extract_path.display());
-            let mut output_file = File::create(extract_path)?;
+            let mut output_file = File::create(&extract_path)?;
             std::io::copy(&mut entry, &mut output_file)?;
+            // keep executables executable
+            #[cfg(unix)]
+            if let Some(mode) = entry.unix_mode() {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(&extract_path, std::fs::Permissions::from_mode(mode))?;
+            }
         } else {
             // println!("Creating directory: {}", extract_path.display());
             std::fs::create_dir_all(extract_path)?;
@@ -29,3 +42,34 @@ pub fn unzip(zip_path: &str, dest: &str) -> anyhow::Result<()> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn make_zip(path: &std::path::Path, names: &[&str]) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        for name in names {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(b"data").unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn test_unzip_rejects_escaping_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("out");
+        let good = dir.path().join("good.zip");
+        make_zip(&good, &["plugin/bin/tool"]);
+        unzip(good.to_str().unwrap(), dest.to_str().unwrap()).unwrap();
+        assert!(dest.join("plugin").join("bin").join("tool").is_file());
+
+        let evil = dir.path().join("evil.zip");
+        make_zip(&evil, &["../evil"]);
+        assert!(unzip(evil.to_str().unwrap(), dest.to_str().unwrap()).is_err());
+        assert!(!dir.path().join("evil").exists());
+    }
+}
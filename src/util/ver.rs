@@ -1,4 +1,5 @@
 use semver::{Prerelease, Version};
+use serde::Serialize;
 
 //	a.b.c => a.b.(c+1)-alpha.0
 //	a.b.c-alpha.x => a.b.c-beta.0
@@ -69,6 +70,43 @@ pub fn next_major(mut v: Version) -> Version {
     v
 }
 
+// how far a newer version is ahead, the part next_major/next_minor/next_patch/next_pre would bump
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VersionGap {
+    Prerelease,
+    Patch,
+    Minor,
+    Major,
+}
+
+impl std::fmt::Display for VersionGap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VersionGap::Prerelease => write!(f, "prerelease"),
+            VersionGap::Patch => write!(f, "patch"),
+            VersionGap::Minor => write!(f, "minor"),
+            VersionGap::Major => write!(f, "major"),
+        }
+    }
+}
+
+// none unless to is newer than from
+pub fn version_gap(from: &Version, to: &Version) -> Option<VersionGap> {
+    if to <= from {
+        return None;
+    }
+    if to.major != from.major {
+        Some(VersionGap::Major)
+    } else if to.minor != from.minor {
+        Some(VersionGap::Minor)
+    } else if to.patch != from.patch {
+        Some(VersionGap::Patch)
+    } else {
+        Some(VersionGap::Prerelease)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -131,4 +169,21 @@ mod tests {
         let v = next_major(v);
         assert_eq!(v.to_string(), "2.0.0");
     }
+
+    #[test]
+    fn test_version_gap() {
+        let gap = |a: &str, b: &str| {
+            version_gap(&Version::parse(a).unwrap(), &Version::parse(b).unwrap())
+        };
+        assert_eq!(gap("1.2.3", "2.0.0"), Some(VersionGap::Major));
+        assert_eq!(gap("1.2.3", "1.3.0"), Some(VersionGap::Minor));
+        assert_eq!(gap("1.2.3", "1.2.4"), Some(VersionGap::Patch));
+        assert_eq!(
+            gap("1.2.4-alpha.0", "1.2.4-beta.0"),
+            Some(VersionGap::Prerelease)
+        );
+        assert_eq!(gap("1.2.4-rc.0", "1.2.4"), Some(VersionGap::Prerelease));
+        assert_eq!(gap("1.2.3", "1.2.3"), None);
+        assert_eq!(gap("2.0.0", "1.9.9"), None);
+    }
 }
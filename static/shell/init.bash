@@ -0,0 +1,22 @@
+# cot shell integration for bash, add to ~/.bashrc:
+#   eval "$(cot shell-init bash)"
+export COT_HOME={{cot_home}}
+case ":$PATH:" in
+  *:{{shims}}:*) ;;
+  *) export PATH={{shims}}:"$PATH" ;;
+esac
+
+# re-evaluate the project pins whenever the directory changes
+_cot_hook() {
+  if [[ "${_COT_PWD:-}" != "$PWD" ]]; then
+    _COT_PWD="$PWD"
+    eval "$({{cot}} env --shell bash --hook)"
+  fi
+}
+if [[ ";${PROMPT_COMMAND:-};" != *";_cot_hook;"* ]]; then
+  PROMPT_COMMAND="_cot_hook${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+fi
+
+if [[ $- == *i* ]]; then
+  eval "$({{cot}} completions bash 2>/dev/null)"
+fi
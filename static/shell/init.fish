@@ -0,0 +1,16 @@
+# cot shell integration for fish, add to ~/.config/fish/config.fish:
+#   cot shell-init fish | source
+set -gx COT_HOME {{cot_home}}
+if not contains -- {{shims}} $PATH
+    set -gx PATH {{shims}} $PATH
+end
+
+# re-evaluate the project pins whenever the directory changes
+function _cot_hook --on-variable PWD
+    {{cot}} env --shell fish --hook | source
+end
+_cot_hook
+
+if status is-interactive
+    {{cot}} completions fish 2>/dev/null | source
+end
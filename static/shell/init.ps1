@@ -0,0 +1,22 @@
+# cot shell integration for PowerShell, add to $PROFILE:
+#   cot shell-init pwsh | Out-String | Invoke-Expression
+$env:COT_HOME = {{cot_home}}
+if (-not (($env:PATH -split [IO.Path]::PathSeparator) -contains {{shims}})) {
+    $env:PATH = {{shims}} + [IO.Path]::PathSeparator + $env:PATH
+}
+
+# re-evaluate the project pins whenever the directory changes
+function global:Invoke-CotHook {
+    $here = (Get-Location).Path
+    if ($here -ne $global:CotLocation) {
+        $global:CotLocation = $here
+        & {{cot}} env --shell pwsh --hook | Out-String | Invoke-Expression
+    }
+}
+if (-not $global:CotPrompt) {
+    $global:CotPrompt = $function:prompt
+    function global:prompt { Invoke-CotHook; & $global:CotPrompt }
+}
+Invoke-CotHook
+
+& {{cot}} completions pwsh 2>$null | Out-String | Invoke-Expression
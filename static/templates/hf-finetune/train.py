@@ -0,0 +1,69 @@
+"""Fine-tune a sequence classification model, settings come from config.yaml."""
+
+import sys
+
+import evaluate
+import numpy as np
+import yaml
+from datasets import load_dataset
+from transformers import (
+    AutoModelForSequenceClassification,
+    AutoTokenizer,
+    DataCollatorWithPadding,
+    Trainer,
+    TrainingArguments,
+)
+
+
+def main(config_path: str = "config.yaml") -> None:
+    with open(config_path) as f:
+        config = yaml.safe_load(f)
+
+    tokenizer = AutoTokenizer.from_pretrained(config["model"])
+    model = AutoModelForSequenceClassification.from_pretrained(
+        config["model"], num_labels=config["num_labels"]
+    )
+    dataset = load_dataset(config["dataset"])
+
+    def tokenize(batch):
+        return tokenizer(
+            batch[config["text_column"]],
+            truncation=True,
+            max_length=config["max_length"],
+        )
+
+    dataset = dataset.map(tokenize, batched=True)
+    if config["label_column"] != "labels":
+        dataset = dataset.rename_column(config["label_column"], "labels")
+
+    accuracy = evaluate.load("accuracy")
+
+    def compute_metrics(eval_pred):
+        logits, labels = eval_pred
+        return accuracy.compute(predictions=np.argmax(logits, axis=-1), references=labels)
+
+    args = TrainingArguments(
+        output_dir=config["output_dir"],
+        num_train_epochs=config["epochs"],
+        per_device_train_batch_size=config["batch_size"],
+        per_device_eval_batch_size=config["batch_size"],
+        learning_rate=float(config["learning_rate"]),
+        eval_strategy="epoch",
+        save_strategy="epoch",
+        load_best_model_at_end=True,
+    )
+    trainer = Trainer(
+        model=model,
+        args=args,
+        train_dataset=dataset["train"],
+        eval_dataset=dataset["test"],
+        data_collator=DataCollatorWithPadding(tokenizer),
+        compute_metrics=compute_metrics,
+    )
+    trainer.train()
+    trainer.save_model(config["output_dir"])
+    tokenizer.save_pretrained(config["output_dir"])
+
+
+if __name__ == "__main__":
+    main(*sys.argv[1:])
@@ -0,0 +1,5 @@
+from {{name_snake}} import hello
+
+
+def test_hello():
+    assert hello() == "Hello from {{name}}!"